@@ -0,0 +1,7 @@
+# Messages of the source language, for strings needing plurals or other grammar rules.
+# Message identifiers are string identifiers with dots replaced by dashes.
+
+ui-pages_read = { $pages_read ->
+    [one] { $pages_read } page read
+   *[other] { $pages_read } pages read
+}
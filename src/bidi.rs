@@ -0,0 +1,37 @@
+use std::borrow::Cow;
+use unicode_bidi::{BidiInfo, Level};
+
+use crate::book::TextDirection;
+
+/// Does a text need reordering for display, either because of its base direction or
+/// because it contains right-to-left characters?
+pub fn needs_reorder(text: &str, direction: TextDirection) -> bool {
+    direction == TextDirection::RightToLeft || BidiInfo::new(text, None).has_rtl()
+}
+
+/// Reorder a text from logical order into visual order, for display by the text
+/// renderer which lays out all characters from left to right.
+///
+/// Each row (separated by `\n`) is reordered on its own with the Unicode bidirectional
+/// algorithm, so the text must be wrapped into rows beforehand. Mirrored characters like
+/// brackets are not substituted.
+pub fn reorder(text: &str, direction: TextDirection) -> Cow<'_, str> {
+    if !needs_reorder(text, direction) {
+        return Cow::Borrowed(text);
+    }
+    let level = match direction {
+        TextDirection::LeftToRight => Level::ltr(),
+        TextDirection::RightToLeft => Level::rtl(),
+    };
+    let rows: Vec<String> = text
+        .split('\n')
+        .map(|row| {
+            let info = BidiInfo::new(row, Some(level));
+            info.paragraphs
+                .iter()
+                .map(|paragraph| info.reorder_line(paragraph, paragraph.range.clone()))
+                .collect()
+        })
+        .collect();
+    Cow::Owned(rows.join("\n"))
+}
@@ -0,0 +1,806 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::color::BookColor;
+
+/// Keys of the buttons of a page with several choices, in choice order, for the story
+/// formats converting choices into buttons.
+pub(crate) const CHOICE_KEYS: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum TextAlign {
+    Start,
+    #[default]
+    Center,
+    End,
+    /// Stretch the words of each row to fill the entire text width, except for the last
+    /// row of each paragraph. Requires a text width; otherwise behaves like `Start`.
+    Justify,
+}
+
+impl TextAlign {
+    /// Horizontal alignment of the rows of text inside the text block.
+    pub fn horizontal_align(&self) -> HorizontalAlign {
+        match self {
+            TextAlign::Start | TextAlign::Justify => HorizontalAlign::Left,
+            TextAlign::Center => HorizontalAlign::Center,
+            TextAlign::End => HorizontalAlign::Right,
+        }
+    }
+
+    /// Placement of a line node inside the page column.
+    pub fn align_self(&self) -> AlignSelf {
+        match self {
+            TextAlign::Start | TextAlign::Justify => AlignSelf::FlexStart,
+            TextAlign::Center => AlignSelf::Center,
+            TextAlign::End => AlignSelf::FlexEnd,
+        }
+    }
+
+    /// Placement of the text block inside a line node.
+    pub fn justify_content(&self) -> JustifyContent {
+        match self {
+            TextAlign::Start | TextAlign::Justify => JustifyContent::FlexStart,
+            TextAlign::Center => JustifyContent::Center,
+            TextAlign::End => JustifyContent::FlexEnd,
+        }
+    }
+
+    /// Mirror the alignment for a text direction, so that `Start` and `End` refer to the
+    /// side where rows start and end.
+    pub fn for_direction(self, direction: TextDirection) -> TextAlign {
+        match (self, direction) {
+            (TextAlign::Start, TextDirection::RightToLeft) => TextAlign::End,
+            (TextAlign::End, TextDirection::RightToLeft) => TextAlign::Start,
+            (align, _) => align,
+        }
+    }
+}
+
+/// Writing direction of a language.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextDirection {
+    #[default]
+    LeftToRight,
+    /// Right-to-left, like Arabic or Hebrew. Layouts are mirrored, and text is reordered
+    /// with the Unicode bidirectional algorithm.
+    RightToLeft,
+}
+
+impl TextDirection {
+    /// Direction of a row of UI nodes laid out in reading order.
+    pub fn flex_row(&self) -> FlexDirection {
+        match self {
+            TextDirection::LeftToRight => FlexDirection::Row,
+            TextDirection::RightToLeft => FlexDirection::RowReverse,
+        }
+    }
+}
+
+/// How the design resolution of a book is fitted into the window.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ScaleMode {
+    /// Scale uniformly so the entire design resolution fits inside the window, and
+    /// letterbox the remaining space.
+    #[default]
+    Fit,
+    /// Scale uniformly like [`ScaleMode::Fit`], but let the content expand into the
+    /// remaining space instead of letterboxing it.
+    Expand,
+    /// Scale uniformly so the design width matches the window width.
+    MatchWidth,
+    /// Scale uniformly so the design height matches the window height.
+    MatchHeight,
+}
+
+/// How content overflowing the screen is handled.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum OverflowMode {
+    /// Let the content scroll, with indicators when more content is available.
+    #[default]
+    Scroll,
+    /// Split the page into continuation screens, each with a single "Continue" button;
+    /// only the last screen shows the page buttons.
+    Paginate,
+}
+
+/// What moving to the next page from the last page of a book does.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum EndOfBook {
+    /// End the run and show the scoreboard, as if the last page were final.
+    Scoreboard,
+    /// Wrap around to the first page, continuing the run.
+    Wrap,
+}
+
+/// Size of a screen, in pixels.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Resolution {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Resolution {
+            width: 1280.,
+            height: 720.,
+        }
+    }
+}
+
+/// Named reusable style, referenced by pages, lines and buttons.
+///
+/// All fields are optional; unset fields are inherited from the parent style if any, then
+/// from the enclosing scope (book defaults, then page style).
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct BookStyle {
+    /// Name of a style to inherit unset fields from.
+    pub inherits: Option<String>,
+    /// Text color.
+    pub color: Option<BookColor>,
+    /// Font size.
+    pub size: Option<f32>,
+    /// Horizontal alignment of lines.
+    pub align: Option<TextAlign>,
+    /// Page background color. Only meaningful on page styles.
+    pub background_color: Option<BookColor>,
+    /// Name of the font, from [`Book::fonts`].
+    pub font: Option<String>,
+    /// Use the bold variant of the font.
+    pub bold: Option<bool>,
+    /// Use the italic variant of the font.
+    pub italic: Option<bool>,
+}
+
+impl BookStyle {
+    /// Merge two styles, with the fields of `other` taking precedence over the ones of
+    /// `self`.
+    pub fn merge(&self, other: &BookStyle) -> BookStyle {
+        BookStyle {
+            inherits: None,
+            color: other.color.clone().or_else(|| self.color.clone()),
+            size: other.size.or(self.size),
+            align: other.align.or(self.align),
+            background_color: other
+                .background_color
+                .clone()
+                .or_else(|| self.background_color.clone()),
+            font: other.font.clone().or_else(|| self.font.clone()),
+            bold: other.bold.or(self.bold),
+            italic: other.italic.or(self.italic),
+        }
+    }
+}
+
+/// Fully resolved style, with all values defined.
+#[derive(Clone, PartialEq)]
+pub struct ResolvedStyle {
+    pub color: Color,
+    pub size: f32,
+    pub align: TextAlign,
+    pub background_color: Color,
+    /// Name of the font, or `None` for the built-in font.
+    pub font: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+/// Font family registered by a book, with its variants and fallbacks.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FontFamily {
+    /// Asset path of the regular variant.
+    pub path: String,
+    /// Asset path of the bold variant, if any.
+    pub bold: Option<String>,
+    /// Asset path of the italic variant, if any.
+    pub italic: Option<String>,
+    /// Asset path of the bold italic variant, if any.
+    pub bold_italic: Option<String>,
+    /// Names of other fonts of the book to render the characters this font doesn't
+    /// have (e.g. CJK or emoji), in order of preference.
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+}
+
+/// Language of a book.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Language {
+    /// Language code, e.g. `"fr"`.
+    pub code: String,
+    /// Name displayed in the language picker, e.g. `"Français"`.
+    pub name: String,
+    /// Asset path of the string table of the language. Unused for the source language.
+    pub strings: Option<String>,
+    /// Asset path of the Fluent messages of the language, which take precedence over the
+    /// string table.
+    pub messages: Option<String>,
+    /// Writing direction of the language.
+    #[serde(default)]
+    pub direction: TextDirection,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language {
+            code: "en".to_string(),
+            name: "English".to_string(),
+            strings: None,
+            messages: None,
+            direction: TextDirection::default(),
+        }
+    }
+}
+
+/// Source format of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookFormat {
+    /// Native JSON format.
+    Json,
+    /// Twine 3 source code, imported with [`crate::twee::import`].
+    Twee,
+    /// Rusty Object Notation, with the same structure as JSON.
+    Ron,
+    /// YAML, with the same structure as JSON.
+    Yaml,
+    /// Markdown, parsed with [`crate::markdown::parse`].
+    Markdown,
+    /// Compiled Ink story (`.ink.json`), run by [`crate::ink::InkStory`].
+    Ink,
+}
+
+impl BookFormat {
+    /// Get the format of a book from its file extension, if supported.
+    pub fn from_extension(extension: &str) -> Option<BookFormat> {
+        match extension {
+            "json" => Some(BookFormat::Json),
+            "twee" | "tw" => Some(BookFormat::Twee),
+            "ron" => Some(BookFormat::Ron),
+            "yaml" | "yml" => Some(BookFormat::Yaml),
+            "md" | "markdown" => Some(BookFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    /// Get the format of a book from its file extension and content.
+    ///
+    /// Compiled Ink stories share the `.json` extension of native books, and are told
+    /// apart by their `inkVersion` field.
+    pub fn detect(extension: &str, source: &str) -> Option<BookFormat> {
+        match BookFormat::from_extension(extension)? {
+            BookFormat::Json if source.contains("\"inkVersion\"") => Some(BookFormat::Ink),
+            format => Some(format),
+        }
+    }
+}
+
+/// Where a button leads to, once its action is resolved against the pages of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    /// Page of the given index.
+    Page(usize),
+    /// End of the run, and the scoreboard.
+    End,
+    /// Nowhere, because the action targets an unknown page, or goes past the last page.
+    Missing,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum ButtonAction {
+    NextPage,
+    JumpToPage(String),
+    JumpToEnd,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Line {
+    /// Stable identifier for translation. If `None`, derived from the page and line index.
+    pub id: Option<String>,
+    pub text: String,
+    /// Name of the style of the line, overriding the page style.
+    pub style: Option<String>,
+    pub align: Option<TextAlign>,
+    pub color: Option<BookColor>,
+    pub size: Option<f32>,
+    /// Name of the font, from [`Book::fonts`].
+    pub font: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Button {
+    /// Stable identifier for translation. If `None`, derived from the page and button key.
+    pub id: Option<String>,
+    pub text: String,
+    pub action: ButtonAction,
+    /// Name of the style of the button text, overriding the page style.
+    pub style: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Page {
+    /// Page name, for cross-reference (e.g. [`ButtonAction::JumpToPage`]).
+    pub name: Option<String>,
+    /// Is the page the final message before the scoreboard?
+    #[serde(default)]
+    pub is_final: bool,
+    /// Lines of text to display.
+    pub lines: Vec<Line>,
+    /// Buttons to show on page and their action.
+    pub buttons: Option<HashMap<String, Button>>,
+    /// Page background color.
+    pub background_color: Option<BookColor>,
+    /// Align of page content.
+    pub align: Option<JustifyContent>,
+    /// Default horizontal alignment of lines, overriding [`Book::text_align`].
+    pub text_align: Option<TextAlign>,
+    /// Width of the text column, overriding [`Book::text_width`].
+    pub text_width: Option<f32>,
+    /// Name of the style of the page, applying to its background and as the default for
+    /// its lines and buttons.
+    pub style: Option<String>,
+}
+
+impl Page {
+    /// Get the identifier of a line for translation, for a page of key `page_key`.
+    pub fn line_id(&self, page_key: &str, line_index: usize) -> String {
+        self.lines[line_index]
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("{}.line{}", page_key, line_index))
+    }
+
+    /// Get the identifier of a button of the page for translation, for a page of key
+    /// `page_key`.
+    pub fn button_id(&self, page_key: &str, key: &str) -> String {
+        let button = self.buttons.as_ref().and_then(|buttons| buttons.get(key));
+        button_id(button, page_key, key)
+    }
+}
+
+/// Get the identifier of a button for translation, derived from its page and key if the
+/// button has no explicit identifier.
+fn button_id(button: Option<&Button>, page_key: &str, key: &str) -> String {
+    button
+        .and_then(|button| button.id.clone())
+        .unwrap_or_else(|| format!("{}.button.{}", page_key, key))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Book {
+    pub pages: Vec<Page>,
+    #[serde(default)]
+    pub line_spacing: f32,
+    pub default_buttons: HashMap<String, Button>,
+    /// Default horizontal alignment of lines.
+    #[serde(default)]
+    pub text_align: TextAlign,
+    /// Width of the text column lines wrap into. If `None`, lines only break at explicit
+    /// line breaks, and justified text is not available.
+    pub text_width: Option<f32>,
+    /// Resolution the book is designed for. All sizes are in pixels of this resolution,
+    /// and scaled to the actual window size.
+    #[serde(default)]
+    pub design_resolution: Resolution,
+    /// How the design resolution is fitted into the window.
+    #[serde(default)]
+    pub scale_mode: ScaleMode,
+    /// Color of the letterbox bars with [`ScaleMode::Fit`]. If `None`, the page
+    /// background color extends into the bars.
+    pub letterbox_color: Option<BookColor>,
+    /// How pages too long to fit on screen are handled.
+    #[serde(default)]
+    pub overflow: OverflowMode,
+    /// What moving to the next page from the last page does, if that page isn't final.
+    /// Defaults to [`EndOfBook::Scoreboard`], with a validation warning.
+    pub end_of_book: Option<EndOfBook>,
+    /// Named styles, referenced by pages, lines and buttons.
+    #[serde(default)]
+    pub styles: HashMap<String, BookStyle>,
+    /// Default text color.
+    #[serde(default = "Book::default_color")]
+    pub default_color: BookColor,
+    /// Default font size.
+    #[serde(default = "Book::default_size")]
+    pub default_size: f32,
+    /// Default page background color.
+    #[serde(default = "Book::default_background_color")]
+    pub default_background_color: BookColor,
+    /// Named colors, referenced by name from any color of the book.
+    #[serde(default)]
+    pub palette: HashMap<String, BookColor>,
+    /// Fonts used by the book, by name.
+    #[serde(default)]
+    pub fonts: HashMap<String, FontFamily>,
+    /// Name of the default font. If `None`, the built-in font is used.
+    pub default_font: Option<String>,
+    /// Name of the style of the scoreboard.
+    pub scoreboard_style: Option<String>,
+    /// Language the book is written in.
+    #[serde(default)]
+    pub source_language: Language,
+    /// Languages the book is translated to.
+    #[serde(default)]
+    pub translations: Vec<Language>,
+}
+
+impl Default for Book {
+    fn default() -> Self {
+        Book {
+            pages: vec![],
+            line_spacing: 30.0,
+            default_buttons: HashMap::default(),
+            text_align: TextAlign::default(),
+            text_width: None,
+            design_resolution: Resolution::default(),
+            scale_mode: ScaleMode::default(),
+            letterbox_color: None,
+            overflow: OverflowMode::default(),
+            end_of_book: None,
+            styles: HashMap::default(),
+            default_color: Book::default_color(),
+            default_size: Book::default_size(),
+            default_background_color: Book::default_background_color(),
+            palette: HashMap::default(),
+            fonts: HashMap::default(),
+            default_font: None,
+            scoreboard_style: None,
+            source_language: Language::default(),
+            translations: vec![],
+        }
+    }
+}
+
+impl Book {
+    /// Parse a book from its source text.
+    ///
+    /// Issues which don't prevent loading, like unsupported Twine macros, are logged as
+    /// warnings.
+    pub fn parse(source: &str, format: BookFormat) -> Result<Book, String> {
+        match format {
+            BookFormat::Json => serde_json::from_str(source).map_err(|err| err.to_string()),
+            BookFormat::Ron => ron::from_str(source).map_err(|err| {
+                let position = err.position;
+                format!(
+                    "{} at line {} column {}",
+                    err.code, position.line, position.col
+                )
+            }),
+            BookFormat::Yaml => serde_yaml::from_str(source).map_err(|err| err.to_string()),
+            BookFormat::Twee => {
+                let import = crate::twee::import(source)?;
+                for warning in &import.warnings {
+                    warn!("{}", warning);
+                }
+                Ok(import.book)
+            }
+            BookFormat::Markdown => crate::markdown::parse(source),
+            BookFormat::Ink => {
+                Err("Compiled Ink stories can't be converted into a book".to_string())
+            }
+        }
+    }
+
+    fn default_color() -> BookColor {
+        Color::rgb(0.8, 0.8, 0.8).into()
+    }
+
+    fn default_size() -> f32 {
+        30.
+    }
+
+    fn default_background_color() -> BookColor {
+        Color::rgb(0.1, 0.1, 0.2).into()
+    }
+
+    /// Resolve a color of the book, or get `fallback` if `None`.
+    ///
+    /// Invalid colors also resolve to `fallback`; see [`Book::validate()`].
+    pub fn resolve_color(&self, color: Option<&BookColor>, fallback: Color) -> Color {
+        color
+            .and_then(|color| color.resolve(&self.palette).ok())
+            .unwrap_or(fallback)
+    }
+
+    /// Get all the languages of the book, starting with the source language.
+    pub fn languages(&self) -> impl Iterator<Item = &Language> {
+        std::iter::once(&self.source_language).chain(self.translations.iter())
+    }
+
+    /// Get the index of a page by page name.
+    pub fn page_by_name(&self, name: &str) -> Option<usize> {
+        self.pages
+            .iter()
+            .position(|page| page.name.as_deref() == Some(name))
+    }
+
+    /// Resolve where a button action on the page `page_index` leads to.
+    ///
+    /// All the buttons of a final page lead to the end of the run, and moving to the next
+    /// page from the last page follows [`Book::end_of_book`].
+    pub fn destination(&self, page_index: usize, action: &ButtonAction) -> Destination {
+        if self.pages[page_index].is_final {
+            return Destination::End;
+        }
+        match action {
+            ButtonAction::NextPage if page_index + 1 < self.pages.len() => {
+                Destination::Page(page_index + 1)
+            }
+            ButtonAction::NextPage => match self.end_of_book.unwrap_or(EndOfBook::Scoreboard) {
+                EndOfBook::Scoreboard => Destination::End,
+                EndOfBook::Wrap => Destination::Page(0),
+            },
+            ButtonAction::JumpToPage(name) => self
+                .page_by_name(name)
+                .map_or(Destination::Missing, Destination::Page),
+            ButtonAction::JumpToEnd => Destination::End,
+        }
+    }
+
+    /// Get the key of a page for identifiers, which is its name if any, or its index.
+    pub fn page_key(&self, page_index: usize) -> String {
+        self.pages[page_index]
+            .name
+            .clone()
+            .unwrap_or_else(|| page_index.to_string())
+    }
+
+    /// Get the identifier of a line for translation.
+    ///
+    /// Lines without an explicit identifier get one derived from their position, which
+    /// changes if lines or unnamed pages are inserted before them.
+    pub fn line_id(&self, page_index: usize, line_index: usize) -> String {
+        self.pages[page_index].line_id(&self.page_key(page_index), line_index)
+    }
+
+    /// Get the identifier of a button for translation, from the buttons of the page
+    /// `page_index`, or the default buttons if `None`.
+    pub fn button_id(&self, page_index: Option<usize>, key: &str) -> String {
+        match page_index {
+            Some(page_index) => self.pages[page_index].button_id(&self.page_key(page_index), key),
+            None => button_id(self.default_buttons.get(key), "default", key),
+        }
+    }
+
+    /// Resolve a named style, following its inheritance chain.
+    ///
+    /// Unknown names and inheritance cycles resolve to the empty style for the missing or
+    /// repeated link; see [`Book::validate()`].
+    pub fn style(&self, name: &str) -> BookStyle {
+        let mut chain = vec![];
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if chain.iter().any(|(chain_name, _)| *chain_name == name) {
+                break;
+            }
+            if let Some(style) = self.styles.get(name) {
+                chain.push((name, style));
+                next = style.inherits.as_deref();
+            } else {
+                break;
+            }
+        }
+        chain
+            .iter()
+            .rev()
+            .fold(BookStyle::default(), |style, (_, parent)| {
+                style.merge(parent)
+            })
+    }
+
+    /// Book-level default style.
+    pub fn base_style(&self) -> ResolvedStyle {
+        ResolvedStyle {
+            color: self.resolve_color(Some(&self.default_color), Color::WHITE),
+            size: self.default_size,
+            align: self.text_align,
+            background_color: self
+                .resolve_color(Some(&self.default_background_color), Color::BLACK),
+            font: self.default_font.clone(),
+            bold: false,
+            italic: false,
+        }
+    }
+
+    /// Resolve the style of the scoreboard.
+    pub fn scoreboard_style(&self) -> ResolvedStyle {
+        let style = self
+            .scoreboard_style
+            .as_deref()
+            .map(|name| self.style(name))
+            .unwrap_or_default();
+        self.base_style().apply(&style, self)
+    }
+
+    /// Resolve the style of a page, which is the default for its lines and buttons.
+    ///
+    /// The override order is: book defaults, page style, page inline values.
+    pub fn page_style(&self, page: &Page) -> ResolvedStyle {
+        let style = page
+            .style
+            .as_deref()
+            .map(|name| self.style(name))
+            .unwrap_or_default();
+        let style = style.merge(&BookStyle {
+            align: page.text_align,
+            background_color: page.background_color.clone(),
+            ..Default::default()
+        });
+        self.base_style().apply(&style, self)
+    }
+
+    /// Resolve the style of a line.
+    ///
+    /// The override order is: page style (see [`Book::page_style()`]), line style, line
+    /// inline values.
+    pub fn line_style(&self, page: &Page, line: &Line) -> ResolvedStyle {
+        let style = line
+            .style
+            .as_deref()
+            .map(|name| self.style(name))
+            .unwrap_or_default();
+        let style = style.merge(&BookStyle {
+            color: line.color.clone(),
+            size: line.size,
+            align: line.align,
+            font: line.font.clone(),
+            ..Default::default()
+        });
+        self.page_style(page).apply(&style, self)
+    }
+
+    /// Resolve the style of a button text.
+    ///
+    /// The override order is: page style (see [`Book::page_style()`]), button style.
+    pub fn button_style(&self, page: &Page, button: &Button) -> ResolvedStyle {
+        let style = button
+            .style
+            .as_deref()
+            .map(|name| self.style(name))
+            .unwrap_or_default();
+        self.page_style(page).apply(&style, self)
+    }
+
+    /// Check the book for errors, returning a description of each error found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+
+        for (name, style) in &self.styles {
+            let mut visited = vec![name.as_str()];
+            let mut next = style.inherits.as_deref();
+            while let Some(parent) = next {
+                if visited.contains(&parent) {
+                    errors.push(format!("Style '{}' has an inheritance cycle.", name));
+                    break;
+                }
+                visited.push(parent);
+                next = self
+                    .styles
+                    .get(parent)
+                    .and_then(|style| style.inherits.as_deref());
+            }
+            if let Some(parent) = &style.inherits {
+                if !self.styles.contains_key(parent) {
+                    errors.push(format!(
+                        "Style '{}' inherits from unknown style '{}'.",
+                        name, parent
+                    ));
+                }
+            }
+        }
+
+        let check_font = |errors: &mut Vec<String>, context: &str, font: &Option<String>| {
+            if let Some(font) = font {
+                if !self.fonts.contains_key(font) {
+                    errors.push(format!("{} references unknown font '{}'.", context, font));
+                }
+            }
+        };
+        check_font(&mut errors, "Book default font", &self.default_font);
+        for (name, family) in &self.fonts {
+            for fallback in &family.fallbacks {
+                check_font(
+                    &mut errors,
+                    &format!("Fallbacks of font '{}'", name),
+                    &Some(fallback.clone()),
+                );
+            }
+        }
+        for (name, style) in &self.styles {
+            check_font(&mut errors, &format!("Style '{}'", name), &style.font);
+        }
+
+        let check_style = |errors: &mut Vec<String>, context: &str, style: &Option<String>| {
+            if let Some(style) = style {
+                if !self.styles.contains_key(style) {
+                    errors.push(format!("{} references unknown style '{}'.", context, style));
+                }
+            }
+        };
+        let check_color = |errors: &mut Vec<String>, context: &str, color: Option<&BookColor>| {
+            if let Some(Err(err)) = color.map(|color| color.resolve(&self.palette)) {
+                errors.push(format!("{} has an invalid color: {}.", context, err));
+            }
+        };
+        check_color(&mut errors, "Book default color", Some(&self.default_color));
+        check_color(
+            &mut errors,
+            "Book default background color",
+            Some(&self.default_background_color),
+        );
+        check_color(&mut errors, "Letterbox", self.letterbox_color.as_ref());
+        for (name, color) in &self.palette {
+            check_color(
+                &mut errors,
+                &format!("Palette entry '{}'", name),
+                Some(color),
+            );
+        }
+        for (name, style) in &self.styles {
+            let context = format!("Style '{}'", name);
+            check_color(&mut errors, &context, style.color.as_ref());
+            check_color(&mut errors, &context, style.background_color.as_ref());
+        }
+
+        check_style(&mut errors, "Scoreboard", &self.scoreboard_style);
+        for (key, button) in &self.default_buttons {
+            check_style(
+                &mut errors,
+                &format!("Default button '{}'", key),
+                &button.style,
+            );
+        }
+        for (page_index, page) in self.pages.iter().enumerate() {
+            let page_id = page.name.clone().unwrap_or_else(|| page_index.to_string());
+            let context = format!("Page '{}'", page_id);
+            check_style(&mut errors, &context, &page.style);
+            check_color(&mut errors, &context, page.background_color.as_ref());
+            for (line_index, line) in page.lines.iter().enumerate() {
+                let context = format!("Line #{} of page '{}'", line_index, page_id);
+                check_style(&mut errors, &context, &line.style);
+                check_font(&mut errors, &context, &line.font);
+                check_color(&mut errors, &context, line.color.as_ref());
+            }
+            for (key, button) in page.buttons.iter().flatten() {
+                check_style(
+                    &mut errors,
+                    &format!("Button '{}' of page '{}'", key, page_id),
+                    &button.style,
+                );
+            }
+        }
+
+        // Moving past the last page without saying what it does is likely an oversight
+        if let Some(page) = self.pages.last().filter(|page| !page.is_final) {
+            let buttons = page.buttons.as_ref().unwrap_or(&self.default_buttons);
+            let has_next_page = buttons
+                .values()
+                .any(|button| matches!(button.action, ButtonAction::NextPage));
+            if has_next_page && self.end_of_book.is_none() {
+                errors.push(format!(
+                    "Page '{}' is the last page but isn't final; moving to the next page ends \
+                     the run. Set 'is_final' on the page, or 'end_of_book' on the book.",
+                    self.page_key(self.pages.len() - 1)
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+impl ResolvedStyle {
+    /// Apply a partial style of `book` on top of this one.
+    pub fn apply(&self, style: &BookStyle, book: &Book) -> ResolvedStyle {
+        ResolvedStyle {
+            color: book.resolve_color(style.color.as_ref(), self.color),
+            size: style.size.unwrap_or(self.size),
+            align: style.align.unwrap_or(self.align),
+            background_color: book
+                .resolve_color(style.background_color.as_ref(), self.background_color),
+            font: style.font.clone().or_else(|| self.font.clone()),
+            bold: style.bold.unwrap_or(self.bold),
+            italic: style.italic.unwrap_or(self.italic),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::path::{Path, PathBuf};
+
+use crate::book::{Book, BookFormat};
+use crate::graph::{self, GraphFormat};
+use crate::stats::{self, StatsFormat};
+use crate::{fuzz, html, localization, paths, twee};
+
+/// Path of the default book, relative to the asset directory.
+const DEFAULT_BOOK: &str = "text.json";
+
+/// Run a command-line tool instead of the game, if requested on the command line.
+///
+/// Returns the exit code of the tool, or `None` to run the game.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.get(1)?;
+    let command_args = &args[2..];
+    let exit_code = match command.as_str() {
+        "lint-translations" => lint_translations(command_args),
+        "import-twee" => import_twee(command_args),
+        "export-html" => export_html(command_args),
+        "export-graph" => export_graph(command_args),
+        "analyze-paths" => analyze_paths(command_args),
+        "stats" => book_stats(command_args),
+        "fuzz" => fuzz_book(command_args),
+        _ => return None,
+    };
+    Some(exit_code)
+}
+
+/// Get the lowercase extension of a file path, to detect its format.
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// Load a book from a file path, in the format detected from its extension and content,
+/// and print the warnings of its conversion from other story formats.
+fn load_book(path: &Path) -> Result<Book, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let format = BookFormat::detect(&extension(path), &source).unwrap_or(BookFormat::Json);
+    let book = match format {
+        BookFormat::Twee => twee::import(&source).map(|import| {
+            for warning in &import.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            import.book
+        }),
+        format => Book::parse(&source, format),
+    };
+    book.map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+}
+
+/// Remove the `null` values of the objects of a JSON value, recursively, to keep only the
+/// fields with a value in a serialized book.
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let null_keys: Vec<String> = map
+                .iter()
+                .filter(|(_, value)| value.is_null())
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in null_keys {
+                map.remove(&key);
+            }
+            map.values_mut().for_each(strip_nulls);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// Serialize a book into its native JSON format.
+fn book_to_json(book: &Book) -> String {
+    let mut value = serde_json::to_value(book).unwrap();
+    strip_nulls(&mut value);
+    serde_json::to_string_pretty(&value).unwrap()
+}
+
+/// `import-twee <input> [output]`: convert a Twee 3 source into a JSON book, written to
+/// `output` or the standard output, and report the unsupported features.
+fn import_twee(args: &[String]) -> i32 {
+    let input = match args.first() {
+        Some(input) => input,
+        None => {
+            eprintln!("Usage: import-twee <input.twee> [output.json]");
+            return 2;
+        }
+    };
+    let import = match std::fs::read_to_string(input)
+        .map_err(|err| err.to_string())
+        .and_then(|source| twee::import(&source))
+    {
+        Ok(import) => import,
+        Err(err) => {
+            eprintln!("Failed to import {}: {}", input, err);
+            return 2;
+        }
+    };
+    for warning in &import.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    write_output(args.get(1), &book_to_json(&import.book))
+}
+
+/// Write the output of a tool to the file `output`, or the standard output if `None`,
+/// returning the exit code of the tool.
+fn write_output(output: Option<&String>, text: &str) -> i32 {
+    match output {
+        Some(output) => {
+            if let Err(err) = std::fs::write(output, text) {
+                eprintln!("Failed to write {}: {}", output, err);
+                return 2;
+            }
+        }
+        None => println!("{}", text),
+    }
+    0
+}
+
+/// Get the path of the book of a tool, from the argument `arg` or the default book if
+/// `None`.
+fn tool_book_path(arg: Option<&String>) -> PathBuf {
+    arg.map_or_else(|| Path::new("assets").join(DEFAULT_BOOK), Into::into)
+}
+
+/// Load the book of a tool, from the path `arg` or the default book if `None`.
+fn load_tool_book(arg: Option<&String>) -> Result<Book, i32> {
+    load_book(&tool_book_path(arg)).map_err(|err| {
+        eprintln!("{}", err);
+        2
+    })
+}
+
+/// `export-html [book] [output]`: render a book into a self-contained HTML document,
+/// written to `output` or the standard output.
+fn export_html(args: &[String]) -> i32 {
+    match load_tool_book(args.first()) {
+        Ok(book) => write_output(args.get(1), &html::export(&book)),
+        Err(exit_code) => exit_code,
+    }
+}
+
+/// `lint-translations [book]`: list the untranslated, stale, and unused strings of all
+/// the translations of a book.
+fn lint_translations(args: &[String]) -> i32 {
+    let book = match load_tool_book(args.first()) {
+        Ok(book) => book,
+        Err(exit_code) => return exit_code,
+    };
+    let path = tool_book_path(args.first());
+    let asset_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    if localization::lint_translations(&book, asset_dir) {
+        0
+    } else {
+        1
+    }
+}
+
+/// `export-graph <dot|mermaid> [book] [output]`: render the graph of the pages and
+/// buttons of a book, written to `output` or the standard output.
+fn export_graph(args: &[String]) -> i32 {
+    let format = match args.first().and_then(|name| GraphFormat::from_name(name)) {
+        Some(format) => format,
+        None => {
+            eprintln!("Usage: export-graph <dot|mermaid> [book] [output]");
+            return 2;
+        }
+    };
+    match load_tool_book(args.get(1)) {
+        Ok(book) => write_output(args.get(2), &graph::export(&book, format)),
+        Err(exit_code) => exit_code,
+    }
+}
+
+/// `analyze-paths [book]`: report the endings of a book with the pages read to reach
+/// them, the best score, and the loops and dead ends of the story.
+///
+/// Fails if the book has no ending or dead ends.
+fn analyze_paths(args: &[String]) -> i32 {
+    let book = match load_tool_book(args.first()) {
+        Ok(book) => book,
+        Err(exit_code) => return exit_code,
+    };
+    let report = paths::analyze(&book);
+    print!("{}", report);
+    if report.endings.is_empty() || !report.dead_ends.is_empty() {
+        1
+    } else {
+        0
+    }
+}
+
+/// `stats <text|json> [book] [output]`: report the word counts, reading time, choices
+/// and endings of a book, written to `output` or the standard output.
+fn book_stats(args: &[String]) -> i32 {
+    let format = match args.first().and_then(|name| StatsFormat::from_name(name)) {
+        Some(format) => format,
+        None => {
+            eprintln!("Usage: stats <text|json> [book] [output]");
+            return 2;
+        }
+    };
+    match load_tool_book(args.get(1)) {
+        Ok(book) => write_output(args.get(2), &stats::export(&book, format)),
+        Err(exit_code) => exit_code,
+    }
+}
+
+/// `fuzz [book] [runs] [seed]`: play a book many times headlessly by pressing random
+/// buttons, and report the failed runs, the pages never reached, and the distribution of
+/// the pages read.
+///
+/// Fails if any run panics, gets stuck on a page, or never ends.
+fn fuzz_book(args: &[String]) -> i32 {
+    let path = tool_book_path(args.first());
+    let runs = args.get(1).map_or(Ok(1000), |arg| arg.parse::<usize>());
+    let seed = args.get(2).map_or(Ok(0), |arg| arg.parse::<u64>());
+    let (runs, seed) = match (runs, seed) {
+        (Ok(runs), Ok(seed)) => (runs, seed),
+        _ => {
+            eprintln!("Usage: fuzz [book] [runs] [seed]");
+            return 2;
+        }
+    };
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", path.display(), err);
+            return 2;
+        }
+    };
+    match fuzz::run(&source, &extension(&path), runs, seed) {
+        Ok(report) => {
+            print!("{}", report);
+            if report.is_success() {
+                0
+            } else {
+                1
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            2
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use bevy::prelude::*;
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{collections::HashMap, fmt};
+
+/// Maximum number of palette references followed to resolve a color, to catch cycles.
+const MAX_PALETTE_DEPTH: usize = 16;
+
+/// Color of a book.
+///
+/// Either a Bevy color in its serialized form, like `{"Rgba": {"red": 1.0, ...}}`, or a
+/// string with:
+/// - a hex code, like `"#f80"` or `"#ff8800cc"`;
+/// - an `rgb()`, `rgba()`, `hsl()`, or `hsla()` CSS function, like `"rgb(255, 128, 0)"`
+///   or `"hsl(30 100% 50% / 80%)"`;
+/// - the name of an entry of [`crate::book::Book::palette`], like `"accent"`;
+/// - a CSS named color, like `"orange"`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum BookColor {
+    Value(Color),
+    Text(String),
+}
+
+/// Components of a serialized Bevy color, for any of its variants.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Components {
+    red: Option<f32>,
+    green: Option<f32>,
+    blue: Option<f32>,
+    hue: Option<f32>,
+    saturation: Option<f32>,
+    lightness: Option<f32>,
+    alpha: Option<f32>,
+}
+
+impl Components {
+    fn into_color<E: de::Error>(self, variant: &str) -> Result<Color, E> {
+        let alpha = self.alpha.unwrap_or(1.);
+        let rgb = (self.red, self.green, self.blue);
+        let hsl = (self.hue, self.saturation, self.lightness);
+        match (variant, rgb, hsl) {
+            ("Rgba", (Some(r), Some(g), Some(b)), _) => Ok(Color::rgba(r, g, b, alpha)),
+            ("RgbaLinear", (Some(r), Some(g), Some(b)), _) => {
+                Ok(Color::rgba_linear(r, g, b, alpha))
+            }
+            ("Hsla", _, (Some(h), Some(s), Some(l))) => Ok(Color::hsla(h, s, l, alpha)),
+            _ => Err(E::custom(format!("invalid {} color components", variant))),
+        }
+    }
+}
+
+/// Key of a map, deserialized as an identifier as required by some formats.
+struct Key(String);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = Key;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an identifier")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Key, E> {
+                Ok(Key(value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_identifier(KeyVisitor)
+    }
+}
+
+/// Visitor accepting both a color string, and a serialized Bevy color.
+///
+/// Formats which don't keep the variant name of a Bevy color when deserializing an
+/// untagged value, like RON, only produce its components; those are taken as sRGB or
+/// HSL depending on their names.
+struct BookColorVisitor;
+
+impl<'de> Visitor<'de> for BookColorVisitor {
+    type Value = BookColor;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a color string or a color value")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<BookColor, E> {
+        Ok(BookColor::Text(value.to_string()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<BookColor, A::Error> {
+        let Key(key) = map
+            .next_key()?
+            .ok_or_else(|| de::Error::custom("empty color"))?;
+        let color = match key.as_str() {
+            "Rgba" | "RgbaLinear" | "Hsla" => {
+                let components: Components = map.next_value()?;
+                components.into_color(&key)?
+            }
+            _ => {
+                // Components without variant name
+                let mut values = HashMap::new();
+                values.insert(key, map.next_value::<f32>()?);
+                while let Some((Key(key), value)) = map.next_entry::<Key, f32>()? {
+                    values.insert(key, value);
+                }
+                let components = Components {
+                    red: values.remove("red"),
+                    green: values.remove("green"),
+                    blue: values.remove("blue"),
+                    hue: values.remove("hue"),
+                    saturation: values.remove("saturation"),
+                    lightness: values.remove("lightness"),
+                    alpha: values.remove("alpha"),
+                };
+                if let Some(key) = values.keys().next() {
+                    return Err(de::Error::custom(format!(
+                        "unknown color component '{}'",
+                        key
+                    )));
+                }
+                let variant = if components.hue.is_some() {
+                    "Hsla"
+                } else {
+                    "Rgba"
+                };
+                components.into_color(variant)?
+            }
+        };
+        Ok(BookColor::Value(color))
+    }
+}
+
+impl<'de> Deserialize<'de> for BookColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BookColorVisitor)
+    }
+}
+
+impl From<Color> for BookColor {
+    fn from(color: Color) -> Self {
+        BookColor::Value(color)
+    }
+}
+
+impl BookColor {
+    /// Resolve the color, looking up palette references in `palette`.
+    pub fn resolve(&self, palette: &HashMap<String, BookColor>) -> Result<Color, String> {
+        let mut color = self;
+        for _ in 0..MAX_PALETTE_DEPTH {
+            let text = match color {
+                BookColor::Value(value) => return Ok(*value),
+                BookColor::Text(text) => text.trim(),
+            };
+            match palette.get(text) {
+                Some(entry) => color = entry,
+                None => return parse(text),
+            }
+        }
+        Err(format!("Palette reference cycle from {:?}", self))
+    }
+}
+
+/// Parse a color string which isn't a palette reference.
+pub fn parse(text: &str) -> Result<Color, String> {
+    if let Some(hex) = text.strip_prefix('#') {
+        return Color::hex(hex).map_err(|_| format!("Invalid hex color '{}'", text));
+    }
+    if let Some((function, args)) = text.split_once('(') {
+        let args = args
+            .strip_suffix(')')
+            .ok_or_else(|| format!("Missing closing parenthesis in color '{}'", text))?;
+        return parse_function(function.trim(), args)
+            .ok_or_else(|| format!("Invalid color function '{}'", text));
+    }
+    let name = text.to_lowercase();
+    if name == "transparent" {
+        return Ok(Color::NONE);
+    }
+    CSS_COLORS
+        .iter()
+        .find(|(css_name, _)| *css_name == name)
+        .map(|(_, rgb)| Color::rgb_u8((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8))
+        .ok_or_else(|| format!("Unknown color '{}'", text))
+}
+
+/// Parse a component of a color function, either a plain number or a percentage of
+/// `percent_scale`.
+fn parse_component(arg: &str, percent_scale: f32) -> Option<f32> {
+    match arg.strip_suffix('%') {
+        Some(percent) => Some(percent.parse::<f32>().ok()? / 100. * percent_scale),
+        None => arg.parse().ok(),
+    }
+}
+
+/// Parse the arguments of an `rgb()`, `rgba()`, `hsl()`, or `hsla()` color function,
+/// separated by commas or spaces, with an optional alpha after a `/` in the latter case.
+fn parse_function(function: &str, args: &str) -> Option<Color> {
+    let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    if args.len() != 3 && args.len() != 4 {
+        return None;
+    }
+    let alpha = match args.get(3) {
+        Some(arg) => parse_component(arg, 1.)?,
+        None => 1.,
+    };
+    match function.to_lowercase().as_str() {
+        "rgb" | "rgba" => {
+            let red = parse_component(args[0], 255.)?;
+            let green = parse_component(args[1], 255.)?;
+            let blue = parse_component(args[2], 255.)?;
+            Some(Color::rgba(red / 255., green / 255., blue / 255., alpha))
+        }
+        "hsl" | "hsla" => {
+            let hue = args[0].trim_end_matches("deg").parse::<f32>().ok()?;
+            let saturation = parse_component(args[1], 1.)?;
+            let lightness = parse_component(args[2], 1.)?;
+            Some(Color::hsla(hue.rem_euclid(360.), saturation, lightness, alpha).as_rgba())
+        }
+        _ => None,
+    }
+}
+
+/// CSS named colors, with their `0xRRGGBB` value.
+const CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that a color has the given sRGB components, within rounding errors.
+    fn assert_rgba(color: Color, expected: [f32; 4]) {
+        let actual = color.as_rgba_f32();
+        let is_close = actual
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| (actual - expected).abs() < 1e-3);
+        assert!(is_close, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn parses_hex_codes() {
+        assert_rgba(parse("#f00").unwrap(), [1., 0., 0., 1.]);
+        assert_rgba(parse("#ff000080").unwrap(), [1., 0., 0., 128. / 255.]);
+        assert_rgba(parse("#00FF00").unwrap(), [0., 1., 0., 1.]);
+        assert_eq!(parse("#12345").unwrap_err(), "Invalid hex color '#12345'");
+        assert!(parse("#ggg").is_err());
+    }
+
+    #[test]
+    fn parses_functions() {
+        assert_rgba(parse("rgb(255 0 0 / 50%)").unwrap(), [1., 0., 0., 0.5]);
+        assert_rgba(parse("rgba(0, 51, 100%, 0.2)").unwrap(), [0., 0.2, 1., 0.2]);
+        assert_rgba(
+            parse("hsl(120deg, 50%, 50%)").unwrap(),
+            [0.25, 0.75, 0.25, 1.],
+        );
+        assert_rgba(parse("HSLA(-240 100% 50% / 1)").unwrap(), [0., 1., 0., 1.]);
+        assert_eq!(
+            parse("rgb(1,2)").unwrap_err(),
+            "Invalid color function 'rgb(1,2)'"
+        );
+        assert!(parse("rgb(1, 2, x)").is_err());
+        assert!(parse("lab(50 0 0)").is_err());
+        assert_eq!(
+            parse("rgb(1, 2, 3").unwrap_err(),
+            "Missing closing parenthesis in color 'rgb(1, 2, 3'"
+        );
+    }
+
+    #[test]
+    fn resolves_names_and_palette() {
+        let palette: HashMap<String, BookColor> = [
+            ("accent", BookColor::Text("primary".to_string())),
+            ("primary", BookColor::Text(" Orange ".to_string())),
+            ("loop", BookColor::Text("loop".to_string())),
+        ]
+        .into_iter()
+        .map(|(name, color)| (name.to_string(), color))
+        .collect();
+        let resolve = |text: &str| BookColor::Text(text.to_string()).resolve(&palette);
+
+        assert_rgba(resolve("rebeccapurple").unwrap(), [0.4, 0.2, 0.6, 1.]);
+        assert_rgba(resolve("transparent").unwrap(), [0., 0., 0., 0.]);
+        assert_rgba(resolve("accent").unwrap(), [1., 165. / 255., 0., 1.]);
+        assert_eq!(resolve("unknown").unwrap_err(), "Unknown color 'unknown'");
+        assert!(resolve("loop")
+            .unwrap_err()
+            .starts_with("Palette reference cycle"));
+    }
+
+    #[test]
+    fn deserializes_strings_and_values() {
+        let color: BookColor = serde_json::from_str(r##""#fff""##).unwrap();
+        assert_eq!(color, BookColor::Text("#fff".to_string()));
+        let color: BookColor =
+            serde_json::from_str(r#"{ "Rgba": { "red": 1.0, "green": 0.5, "blue": 0.0 } }"#)
+                .unwrap();
+        assert_eq!(color, BookColor::Value(Color::rgba(1., 0.5, 0., 1.)));
+        assert!(serde_json::from_str::<BookColor>(r#"{ "Rgba": { "red": 1.0 } }"#).is_err());
+    }
+}
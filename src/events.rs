@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::text_system::{Score, TextSystem};
+
+/// A book finished loading, before its first page is shown.
+#[derive(Debug, Clone)]
+pub struct BookLoaded;
+
+/// A page was shown, at the start of a run or after a button moved to it.
+///
+/// Not sent again when the same page is only respawned, like after a window resize or a
+/// language change.
+#[derive(Debug, Clone)]
+pub struct PageEntered {
+    /// Key of the page, its name or index.
+    pub page_key: String,
+}
+
+/// The player left a page by pressing one of its buttons.
+#[derive(Debug, Clone)]
+pub struct PageLeft {
+    /// Key of the page, its name or index.
+    pub page_key: String,
+}
+
+/// The player pressed a button of a page, before leaving the page.
+#[derive(Debug, Clone)]
+pub struct ChoiceMade {
+    /// Key of the page of the button.
+    pub page_key: String,
+    /// Key of the button, like `"space"` or `"1"`.
+    pub button_key: String,
+}
+
+/// A run reached the end of the story, before the scoreboard is shown.
+#[derive(Debug, Clone)]
+pub struct RunFinished(pub Score);
+
+/// Event of the story waiting to be sent, in the order the story raised it.
+pub(crate) enum StoryEvent {
+    BookLoaded,
+    PageEntered(String),
+    PageLeft(String),
+    ChoiceMade(String, String),
+    RunFinished(Score),
+}
+
+/// Register the events of the story.
+pub(crate) fn add_events(app: &mut App) {
+    app.add_event::<BookLoaded>()
+        .add_event::<PageEntered>()
+        .add_event::<PageLeft>()
+        .add_event::<ChoiceMade>()
+        .add_event::<RunFinished>();
+}
+
+/// Send the events raised by the story during the frame.
+pub(crate) fn send_events(
+    mut text_system: ResMut<TextSystem>,
+    mut book_loaded: EventWriter<BookLoaded>,
+    mut page_entered: EventWriter<PageEntered>,
+    mut page_left: EventWriter<PageLeft>,
+    mut choice_made: EventWriter<ChoiceMade>,
+    mut run_finished: EventWriter<RunFinished>,
+) {
+    for event in text_system.take_events() {
+        match event {
+            StoryEvent::BookLoaded => book_loaded.send(BookLoaded),
+            StoryEvent::PageEntered(page_key) => page_entered.send(PageEntered { page_key }),
+            StoryEvent::PageLeft(page_key) => page_left.send(PageLeft { page_key }),
+            StoryEvent::ChoiceMade(page_key, button_key) => choice_made.send(ChoiceMade {
+                page_key,
+                button_key,
+            }),
+            StoryEvent::RunFinished(score) => run_finished.send(RunFinished(score)),
+        }
+    }
+}
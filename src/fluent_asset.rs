@@ -0,0 +1,61 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use fluent_bundle::FluentResource;
+use std::sync::Arc;
+
+/// A Project Fluent message file.
+#[derive(TypeUuid)]
+#[uuid = "5b4b9b4e-7b0e-4c43-a4f4-6d7e0b1a3c2f"]
+pub struct FluentAsset {
+    pub resource: Arc<FluentResource>,
+}
+
+/// Asset loader for parsing `*.ftl` into a [`FluentAsset`].
+///
+/// Entries with syntax errors are skipped with a warning; the rest of the file is still
+/// usable.
+#[derive(Default)]
+struct FluentAssetLoader;
+
+impl AssetLoader for FluentAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let s = std::str::from_utf8(bytes)?;
+            let resource = match FluentResource::try_new(s.to_owned()) {
+                Ok(resource) => resource,
+                Err((resource, errors)) => {
+                    for error in errors {
+                        warn!("{}: {}", load_context.path().display(), error);
+                    }
+                    resource
+                }
+            };
+            load_context.set_default_asset(LoadedAsset::new(FluentAsset {
+                resource: Arc::new(resource),
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ftl"]
+    }
+}
+
+/// Plugin to register the [`FluentAsset`] and its loader.
+pub struct FluentAssetPlugin;
+
+impl Plugin for FluentAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<FluentAsset>()
+            .init_asset_loader::<FluentAssetLoader>();
+    }
+}
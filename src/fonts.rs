@@ -0,0 +1,199 @@
+use ab_glyph::Font as _;
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::book::{Book, ResolvedStyle};
+
+/// Loaded handles of the variants of a font family.
+struct FontHandles {
+    regular: Handle<Font>,
+    bold: Option<Handle<Font>>,
+    italic: Option<Handle<Font>>,
+    bold_italic: Option<Handle<Font>>,
+}
+
+impl FontHandles {
+    /// Get the closest variant available, falling back to the regular one.
+    fn variant(&self, bold: bool, italic: bool) -> &Handle<Font> {
+        let variant = match (bold, italic) {
+            (true, true) => self
+                .bold_italic
+                .as_ref()
+                .or(self.bold.as_ref())
+                .or(self.italic.as_ref()),
+            (true, false) => self.bold.as_ref(),
+            (false, true) => self.italic.as_ref(),
+            (false, false) => None,
+        };
+        variant.unwrap_or(&self.regular)
+    }
+}
+
+/// Fonts of a book, loaded from their asset paths.
+#[derive(Default)]
+pub struct BookFonts {
+    /// Built-in font, used when no book font is selected, and as last fallback.
+    builtin: Handle<Font>,
+    families: HashMap<String, FontHandles>,
+    fallbacks: HashMap<String, Vec<String>>,
+    /// Fonts which failed to load, left out of the font chains.
+    failed: HashSet<Handle<Font>>,
+}
+
+impl BookFonts {
+    /// Create a font collection with only the built-in font.
+    pub fn new(builtin: Handle<Font>) -> Self {
+        BookFonts {
+            builtin,
+            ..Default::default()
+        }
+    }
+
+    /// Start loading all the fonts of a book.
+    pub fn load(&mut self, book: &Book, asset_server: &AssetServer) {
+        self.families.clear();
+        self.fallbacks.clear();
+        self.failed.clear();
+        for (name, family) in &book.fonts {
+            let load = |path: &String| asset_server.load(path.as_str());
+            self.families.insert(
+                name.clone(),
+                FontHandles {
+                    regular: load(&family.path),
+                    bold: family.bold.as_ref().map(load),
+                    italic: family.italic.as_ref().map(load),
+                    bold_italic: family.bold_italic.as_ref().map(load),
+                },
+            );
+            self.fallbacks
+                .insert(name.clone(), family.fallbacks.clone());
+        }
+    }
+
+    /// Get the handles of all the variants of the fonts of the book.
+    fn handles(&self) -> impl Iterator<Item = &Handle<Font>> {
+        self.families.values().flat_map(|family| {
+            std::iter::once(&family.regular)
+                .chain(&family.bold)
+                .chain(&family.italic)
+                .chain(&family.bold_italic)
+        })
+    }
+
+    /// Check if a font handle is used by this collection.
+    pub fn contains(&self, handle: &Handle<Font>) -> bool {
+        *handle == self.builtin || self.handles().any(|other| other == handle)
+    }
+
+    /// Record the fonts which failed to load, like from a wrong path, and return whether
+    /// any failed since the last call.
+    pub fn update_failed(&mut self, asset_server: &AssetServer) -> bool {
+        let failed: Vec<Handle<Font>> = self
+            .handles()
+            .filter(|handle| {
+                !self.failed.contains(*handle)
+                    && asset_server.get_load_state(*handle) == LoadState::Failed
+            })
+            .cloned()
+            .collect();
+        let has_failed = !failed.is_empty();
+        self.failed.extend(failed);
+        has_failed
+    }
+
+    /// Get the chain of fonts to render a style with, in order of preference: the style
+    /// font, its fallbacks, then the built-in font. Fonts which failed to load are skipped.
+    pub fn chain(&self, style: &ResolvedStyle) -> Vec<Handle<Font>> {
+        let mut chain = vec![];
+        if let Some(name) = &style.font {
+            let mut names = vec![name.as_str()];
+            if let Some(fallbacks) = self.fallbacks.get(name) {
+                names.extend(fallbacks.iter().map(String::as_str));
+            }
+            for name in names {
+                if let Some(family) = self.families.get(name) {
+                    let handle = family.variant(style.bold, style.italic);
+                    if !self.failed.contains(handle) && !chain.contains(handle) {
+                        chain.push(handle.clone());
+                    }
+                }
+            }
+        }
+        if !chain.contains(&self.builtin) {
+            chain.push(self.builtin.clone());
+        }
+        chain
+    }
+
+    /// Get the primary font to render a style with.
+    pub fn primary(&self, style: &ResolvedStyle) -> Handle<Font> {
+        self.chain(style).swap_remove(0)
+    }
+
+    /// Build the text sections to render some text with a style, splitting the text into
+    /// runs of characters each rendered with the first font of the chain which has glyphs
+    /// for them.
+    ///
+    /// Fonts not loaded yet are assumed to have all glyphs.
+    pub fn sections(
+        &self,
+        text: &str,
+        style: &ResolvedStyle,
+        font_size: f32,
+        fonts: &Assets<Font>,
+    ) -> Vec<TextSection> {
+        let chain = self.chain(style);
+        let font_for = |c: char| -> usize {
+            chain
+                .iter()
+                .position(|handle| {
+                    fonts
+                        .get(handle)
+                        .is_none_or(|font| font.font.glyph_id(c).0 != 0)
+                })
+                .unwrap_or(0)
+        };
+
+        let mut sections: Vec<TextSection> = vec![];
+        let mut run = String::new();
+        let mut run_font = None;
+        for c in text.chars() {
+            let font = if c.is_whitespace() {
+                // Keep whitespace in the current run
+                run_font.unwrap_or(0)
+            } else {
+                font_for(c)
+            };
+            if let Some(run_font) = run_font.filter(|run_font| *run_font != font) {
+                let value = std::mem::take(&mut run);
+                sections.push(section(value, &chain[run_font], style, font_size));
+            }
+            run_font = Some(font);
+            run.push(c);
+        }
+        sections.push(section(
+            run,
+            &chain[run_font.unwrap_or(0)],
+            style,
+            font_size,
+        ));
+        sections
+    }
+}
+
+fn section(
+    value: String,
+    font: &Handle<Font>,
+    style: &ResolvedStyle,
+    font_size: f32,
+) -> TextSection {
+    TextSection {
+        value,
+        style: TextStyle {
+            font: font.clone(),
+            font_size,
+            color: style.color,
+        },
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::headless::Playthrough;
+
+/// Maximum number of button presses of a single run, after which the run is considered
+/// to never end.
+const MAX_PRESSES: usize = 1000;
+
+/// Width of the bars of the `page_read` histogram, in characters, for the most frequent
+/// value.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Pseudo-random number generator (SplitMix64), deterministic for a given seed.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a random index below `count`.
+    fn index(&mut self, count: usize) -> usize {
+        (self.next() % count as u64) as usize
+    }
+}
+
+/// Kind of failure of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// The game panicked.
+    Panic,
+    /// The current page has no button which can be pressed.
+    Stuck,
+    /// The run didn't end after [`MAX_PRESSES`] presses.
+    Endless,
+}
+
+/// Failure of one or more runs, with the first run to reproduce it.
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+    /// Number of runs which failed this way.
+    pub count: usize,
+    /// Index of the first run which failed this way.
+    pub run: usize,
+    /// Buttons pressed by the first run which failed this way, in order.
+    pub buttons: Vec<&'static str>,
+}
+
+/// Result of random playthroughs of a book.
+pub struct FuzzReport {
+    pub seed: u64,
+    pub runs: usize,
+    /// Number of runs reaching the scoreboard, by pages read.
+    pub page_read: BTreeMap<u32, usize>,
+    /// Failures, by kind then message.
+    pub failures: Vec<Failure>,
+    /// Keys of the pages of the book never shown.
+    pub unreached: Vec<String>,
+}
+
+impl FuzzReport {
+    /// Did all the runs reach the scoreboard?
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn record_failure(
+        &mut self,
+        kind: FailureKind,
+        message: String,
+        run: usize,
+        buttons: &[&'static str],
+    ) {
+        let failure = self
+            .failures
+            .iter_mut()
+            .find(|failure| failure.kind == kind && failure.message == message);
+        match failure {
+            Some(failure) => failure.count += 1,
+            None => self.failures.push(Failure {
+                kind,
+                message,
+                count: 1,
+                run,
+                buttons: buttons.to_vec(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for FuzzReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ended: usize = self.page_read.values().sum();
+        writeln!(
+            f,
+            "{} run(s) with seed {}: {} ended, {} failed",
+            self.runs,
+            self.seed,
+            ended,
+            self.runs - ended
+        )?;
+        for failure in &self.failures {
+            writeln!(
+                f,
+                "- {:?} x{}: {}",
+                failure.kind,
+                failure.count,
+                failure.message.replace('\n', " ")
+            )?;
+            writeln!(
+                f,
+                "  first in run {}, pressing: {}",
+                failure.run,
+                failure.buttons.join(" ")
+            )?;
+        }
+        if !self.unreached.is_empty() {
+            writeln!(f, "pages never reached: {}", self.unreached.join(", "))?;
+        }
+        if let Some(&most) = self.page_read.values().max() {
+            writeln!(f, "pages read:")?;
+            for (page_read, count) in &self.page_read {
+                let width = (count * HISTOGRAM_WIDTH).div_ceil(most);
+                writeln!(
+                    f,
+                    "{:>5} | {:<width$} {}",
+                    page_read,
+                    "#".repeat(width),
+                    count,
+                    width = HISTOGRAM_WIDTH
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the message of a panic from its payload.
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Play a book `runs` times headlessly, pressing random buttons among the ones the current
+/// page offers, and report the runs which don't end normally, the pages never reached,
+/// and the distribution of the pages read.
+///
+/// Runs are reproducible for a given `seed`. The game is restarted from the scoreboard
+/// after a normal end, and from scratch after a failure.
+pub fn run(source: &str, extension: &str, runs: usize, seed: u64) -> Result<FuzzReport, String> {
+    let mut random = Random(seed);
+    let mut report = FuzzReport {
+        seed,
+        runs,
+        page_read: BTreeMap::new(),
+        failures: vec![],
+        unreached: vec![],
+    };
+    let mut reached = BTreeSet::new();
+    let mut page_keys = vec![];
+    let mut current: Option<Playthrough> = None;
+
+    for run in 0..runs {
+        let playthrough = match &mut current {
+            Some(playthrough) => playthrough,
+            None => match panic::catch_unwind(|| Playthrough::new(source, extension)) {
+                Ok(playthrough) => {
+                    let book = playthrough.book().ok_or_else(|| {
+                        let err = playthrough.load_error().unwrap_or("not loaded");
+                        format!("Failed to load the book: {}", err)
+                    })?;
+                    page_keys = (0..book.pages.len()).map(|i| book.page_key(i)).collect();
+                    current.insert(playthrough)
+                }
+                Err(payload) => {
+                    return Err(format!(
+                        "Failed to start the game: {}",
+                        panic_message(payload)
+                    ));
+                }
+            },
+        };
+        reached.extend(playthrough.page_key());
+
+        let mut buttons = vec![];
+        let failure = loop {
+            if buttons.len() >= MAX_PRESSES {
+                let message = format!("no end after {} presses", MAX_PRESSES);
+                break Some((FailureKind::Endless, message));
+            }
+            let names = playthrough.button_names();
+            if names.is_empty() {
+                let page_key = playthrough.page_key().unwrap_or_default();
+                let message = format!("no button on page '{}'", page_key);
+                break Some((FailureKind::Stuck, message));
+            }
+            let name = names[random.index(names.len())];
+            buttons.push(name);
+            let press = panic::catch_unwind(AssertUnwindSafe(|| playthrough.press_button(name)));
+            if let Err(payload) = press {
+                break Some((FailureKind::Panic, panic_message(payload)));
+            }
+            if playthrough.is_scoreboard() {
+                *report.page_read.entry(playthrough.page_read()).or_default() += 1;
+                // Restart from the scoreboard for the next run
+                let restart =
+                    panic::catch_unwind(AssertUnwindSafe(|| playthrough.press_button("space")));
+                break restart
+                    .err()
+                    .map(|payload| (FailureKind::Panic, panic_message(payload)));
+            }
+            reached.extend(playthrough.page_key());
+        };
+
+        // Restart from scratch after a failure, as the game state may be broken
+        if let Some((kind, message)) = failure {
+            report.record_failure(kind, message, run, &buttons);
+            current = None;
+        }
+    }
+
+    report.unreached = page_keys
+        .into_iter()
+        .filter(|page_key| !reached.contains(page_key))
+        .collect();
+    report
+        .failures
+        .sort_by(|a, b| (a.kind, &a.message).cmp(&(b.kind, &b.message)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzz_books() {
+        let source = include_str!("../assets/text.json");
+        let report = run(source, "json", 200, 0).unwrap();
+        assert!(report.is_success());
+        assert!(report.unreached.is_empty());
+        assert_eq!(report.page_read.values().sum::<usize>(), 200);
+        assert_eq!(report.page_read.keys().next(), Some(&3));
+        assert_eq!(report.page_read.keys().last(), Some(&10));
+
+        // Moving to the next page past the last page ends the run
+        let source = r#"{
+            "default_buttons": { "space": { "text": "Continue", "action": "NextPage" } },
+            "pages": [ { "lines": [ { "text": "The only page" } ] } ]
+        }"#;
+        let report = run(source, "json", 10, 0).unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.page_read.get(&1), Some(&10));
+
+        // A page without any button
+        let source =
+            r#"{ "default_buttons": {}, "pages": [ { "lines": [ { "text": "Stuck" } ] } ] }"#;
+        let report = run(source, "json", 10, 0).unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].kind, FailureKind::Stuck);
+        assert_eq!(report.failures[0].count, 10);
+
+        // A book failing to load
+        let err = run(r#"{ "pages": 3 }"#, "json", 5, 0).err().unwrap();
+        assert!(err.starts_with("Failed to load the book: "), "{}", err);
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Write;
+
+use crate::book::*;
+
+/// Output format of a story graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz DOT language.
+    Dot,
+    /// Mermaid flowchart.
+    Mermaid,
+}
+
+impl GraphFormat {
+    /// Get a format from its name on the command line.
+    pub fn from_name(name: &str) -> Option<GraphFormat> {
+        match name {
+            "dot" => Some(GraphFormat::Dot),
+            "mermaid" => Some(GraphFormat::Mermaid),
+            _ => None,
+        }
+    }
+}
+
+/// Edge of the story graph, for a button of a page.
+struct Edge {
+    from: usize,
+    to: Destination,
+    label: String,
+    /// Is the edge the implicit move to the next page in book order?
+    is_next_page: bool,
+}
+
+/// Collect the edges of the graph of a book, in page and button key order.
+fn edges(book: &Book) -> Vec<Edge> {
+    let mut edges = vec![];
+    for (page_index, page) in book.pages.iter().enumerate() {
+        let buttons = page.buttons.as_ref().unwrap_or(&book.default_buttons);
+        let mut buttons: Vec<_> = buttons.iter().collect();
+        buttons.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, button) in buttons {
+            edges.push(Edge {
+                from: page_index,
+                to: book.destination(page_index, &button.action),
+                label: format!("{}: {}", key, button.text),
+                is_next_page: matches!(button.action, ButtonAction::NextPage) && !page.is_final,
+            });
+        }
+    }
+    edges
+}
+
+/// Escape a label for a double-quoted DOT string.
+fn escape_dot(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Escape a label for a double-quoted Mermaid string.
+fn escape_mermaid(label: &str) -> String {
+    label.replace('"', "#quot;").replace('\n', "<br>")
+}
+
+/// Get the identifier of the node a destination leads to.
+fn node_id(destination: Destination) -> String {
+    match destination {
+        Destination::Page(index) => format!("p{}", index),
+        // `end` is a keyword in Mermaid
+        Destination::End => "story_end".to_string(),
+        Destination::Missing => "missing".to_string(),
+    }
+}
+
+/// Render the graph of the pages of a book and the buttons between them.
+///
+/// Nodes are labelled by page name or index, and edges by button key and text. Final
+/// pages are highlighted, implicit moves to the next page are dashed, and buttons leading
+/// nowhere point to a separate "missing" node.
+pub fn export(book: &Book, format: GraphFormat) -> String {
+    let edges = edges(book);
+    let has_missing = edges.iter().any(|edge| edge.to == Destination::Missing);
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            writeln!(out, "digraph story {{").unwrap();
+            writeln!(out, "    node [shape=box];").unwrap();
+            for (page_index, page) in book.pages.iter().enumerate() {
+                let style = if page.is_final {
+                    ", style=filled, fillcolor=gold, peripheries=2"
+                } else {
+                    ""
+                };
+                let label = escape_dot(&book.page_key(page_index));
+                writeln!(out, "    p{} [label=\"{}\"{}];", page_index, label, style).unwrap();
+            }
+            writeln!(out, "    story_end [label=\"End\", shape=doublecircle];").unwrap();
+            if has_missing {
+                writeln!(
+                    out,
+                    "    missing [label=\"Missing\", shape=octagon, color=red];"
+                )
+                .unwrap();
+            }
+            for edge in &edges {
+                let style = if edge.is_next_page {
+                    ", style=dashed"
+                } else {
+                    ""
+                };
+                writeln!(
+                    out,
+                    "    p{} -> {} [label=\"{}\"{}];",
+                    edge.from,
+                    node_id(edge.to),
+                    escape_dot(&edge.label),
+                    style
+                )
+                .unwrap();
+            }
+            writeln!(out, "}}").unwrap();
+        }
+        GraphFormat::Mermaid => {
+            writeln!(out, "flowchart TD").unwrap();
+            for page_index in 0..book.pages.len() {
+                let label = escape_mermaid(&book.page_key(page_index));
+                writeln!(out, "    p{}[\"{}\"]", page_index, label).unwrap();
+            }
+            writeln!(out, "    story_end((End))").unwrap();
+            if has_missing {
+                writeln!(out, "    missing{{{{Missing}}}}").unwrap();
+            }
+            for edge in &edges {
+                let arrow = if edge.is_next_page { "-.->" } else { "-->" };
+                writeln!(
+                    out,
+                    "    p{} {}|\"{}\"| {}",
+                    edge.from,
+                    arrow,
+                    escape_mermaid(&edge.label),
+                    node_id(edge.to)
+                )
+                .unwrap();
+            }
+            writeln!(out, "    classDef final fill:gold,stroke-width:3px").unwrap();
+            let finals: Vec<String> = book
+                .pages
+                .iter()
+                .enumerate()
+                .filter(|(_, page)| page.is_final)
+                .map(|(page_index, _)| format!("p{}", page_index))
+                .collect();
+            if !finals.is_empty() {
+                writeln!(out, "    class {} final", finals.join(",")).unwrap();
+            }
+            if has_missing {
+                writeln!(out, "    style missing stroke:red").unwrap();
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,702 @@
+#[cfg(test)]
+use bevy::{app::Events, ecs::system::Resource};
+use bevy::{prelude::*, window::WindowResized};
+use chrono::prelude::*;
+#[cfg(test)]
+use std::fmt::Write;
+
+use crate::book::Book;
+use crate::events;
+use crate::fluent_asset::FluentAssetPlugin;
+use crate::state::{self, GameState};
+use crate::text_asset::{TextAsset, TextAssetPlugin};
+use crate::text_system::{TextSystem, BUTTON_KEYS};
+
+/// Maximum number of frames to wait for the strings of a book to load, at about one
+/// millisecond per frame.
+const LOAD_TIMEOUT_FRAMES: usize = 5000;
+
+/// Label of the stage running the [`TextSystem`].
+const PLAYTHROUGH_STAGE: &str = "playthrough";
+
+/// Label of the stage sending the events of the story, after [`PLAYTHROUGH_STAGE`].
+const EVENTS_STAGE: &str = "playthrough_events";
+
+/// Headless playthrough of a book, running the [`TextSystem`] without window, GPU, or
+/// audio, and fed with scripted key presses.
+///
+/// Assets referenced by the book (fonts, translations) load from the `assets` directory
+/// as in the game, but textures are never loaded and no text is rendered.
+pub struct Playthrough {
+    app: App,
+}
+
+impl Playthrough {
+    /// Start a playthrough of a book from its source, in the format of a file of
+    /// extension `extension` (e.g. `"json"`).
+    pub fn new(source: &str, extension: &str) -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_plugin(TextAssetPlugin)
+            .add_plugin(FluentAssetPlugin)
+            .add_asset::<Font>()
+            .add_event::<WindowResized>()
+            .init_resource::<Input<KeyCode>>()
+            // Run on the calling thread, so that panics can be caught
+            .add_stage_after(
+                CoreStage::Update,
+                PLAYTHROUGH_STAGE,
+                SystemStage::single_threaded(),
+            )
+            .add_stage_after(
+                PLAYTHROUGH_STAGE,
+                EVENTS_STAGE,
+                SystemStage::single_threaded().with_system(events::send_events),
+            );
+        events::add_events(&mut app);
+        state::add_systems(&mut app, PLAYTHROUGH_STAGE);
+
+        let content = app
+            .world
+            .get_resource_mut::<Assets<TextAsset>>()
+            .unwrap()
+            .add(TextAsset {
+                value: source.to_string(),
+                extension: extension.to_string(),
+            });
+        let buttons = BUTTON_KEYS
+            .iter()
+            .map(|(name, _)| (name.to_string(), Handle::default()))
+            .collect();
+        let mut text_system =
+            TextSystem::new(content, Handle::default(), buttons, Vec2::new(1280., 720.));
+        // Record all scores at the same date, for reproducible scoreboards
+        text_system.clock = || Utc.ymd(2022, 4, 1).and_hms(12, 0, 0);
+        app.insert_resource(text_system);
+
+        // Load the book and spawn its first page, then wait for its strings to load so
+        // that the content doesn't change during the playthrough
+        app.update();
+        let mut playthrough = Playthrough { app };
+        for _ in 0..LOAD_TIMEOUT_FRAMES {
+            if !playthrough.text_system().localization.is_loading() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            playthrough.app.update();
+        }
+        playthrough
+    }
+
+    #[cfg(test)]
+    /// Start a playthrough of a book file of the `assets` directory.
+    pub fn from_asset(path: &str) -> Self {
+        let full_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join(path);
+        let source = std::fs::read_to_string(&full_path)
+            .unwrap_or_else(|err| panic!("Failed to read {}: {}", full_path.display(), err));
+        let extension = full_path
+            .extension()
+            .map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase());
+        Playthrough::new(&source, &extension)
+    }
+
+    fn text_system(&self) -> &TextSystem {
+        self.app.world.get_resource::<TextSystem>().unwrap()
+    }
+
+    /// Press and release a key, running a single frame with the key just pressed.
+    pub fn press(&mut self, key_code: KeyCode) {
+        self.press_together(&[key_code]);
+    }
+
+    /// Press and release several keys at once, running a single frame with all the keys
+    /// just pressed.
+    pub fn press_together(&mut self, key_codes: &[KeyCode]) {
+        let mut input = self.app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+        for key_code in key_codes {
+            input.press(*key_code);
+        }
+        self.app.update();
+        let mut input = self.app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+        for key_code in key_codes {
+            input.release(*key_code);
+        }
+        input.clear();
+    }
+
+    /// Press the key of a button by name, like `"space"` or `"1"`.
+    ///
+    /// Panics if no key has this name.
+    pub fn press_button(&mut self, name: &str) {
+        let (_, key_code) = BUTTON_KEYS
+            .iter()
+            .find(|(key_name, _)| *key_name == name)
+            .unwrap_or_else(|| panic!("Unknown button key '{}'", name));
+        self.press(*key_code);
+    }
+
+    #[cfg(test)]
+    /// Press the keys of a sequence of buttons, returning the key of the page shown after
+    /// each press, or `None` for the scoreboard.
+    pub fn play(&mut self, names: &[&str]) -> Vec<Option<String>> {
+        names
+            .iter()
+            .map(|name| {
+                self.press_button(name);
+                self.page_key()
+            })
+            .collect()
+    }
+
+    /// Get the key of the page currently shown, or `None` if the scoreboard is shown.
+    pub fn page_key(&self) -> Option<String> {
+        if self.is_scoreboard() {
+            return None;
+        }
+        let text_system = self.text_system();
+        text_system.story.as_ref().map(|story| story.page_key())
+    }
+
+    /// Get the book played, once loaded.
+    pub fn book(&self) -> Option<&Book> {
+        self.text_system().book()
+    }
+
+    /// Get the error of the book played, if it failed to load.
+    pub fn load_error(&self) -> Option<&str> {
+        self.text_system().load_error.as_deref()
+    }
+
+    /// Get the number of pages read in the current run.
+    pub fn page_read(&self) -> u32 {
+        self.text_system().page_read
+    }
+
+    #[cfg(test)]
+    /// Get the current game state.
+    pub fn state(&self) -> GameState {
+        let state = self.app.world.get_resource::<State<GameState>>().unwrap();
+        *state.current()
+    }
+
+    /// Is the scoreboard shown, even under a menu?
+    pub fn is_scoreboard(&self) -> bool {
+        let state = self.app.world.get_resource::<State<GameState>>().unwrap();
+        *state::screen(state) == GameState::Scoreboard
+    }
+
+    /// Get the names of the buttons which can currently be pressed, in key order: the
+    /// buttons of the current page, or the continue and restart buttons.
+    pub fn button_names(&self) -> Vec<&'static str> {
+        let text_system = self.text_system();
+        let is_continuation = text_system.screen_index + 1 < text_system.screens.len();
+        if self.is_scoreboard() || is_continuation {
+            return vec!["space"];
+        }
+        let page = match text_system.current_page() {
+            Some(page) => page,
+            None => return vec![],
+        };
+        let book = text_system.book().unwrap();
+        let buttons = page.buttons.as_ref().unwrap_or(&book.default_buttons);
+        let mut names: Vec<&'static str> = BUTTON_KEYS
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| buttons.contains_key(*name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[cfg(test)]
+    /// Take the events of type `T` sent since the last call, like
+    /// [`crate::PageEntered`].
+    ///
+    /// Events are dropped two frames after being sent, so take them soon after the presses
+    /// sending them.
+    pub fn take_events<T: Resource>(&mut self) -> Vec<T> {
+        let mut events = self.app.world.get_resource_mut::<Events<T>>().unwrap();
+        events.drain().collect()
+    }
+
+    #[cfg(test)]
+    /// Get the text of all the text entities currently spawned, in hierarchy order.
+    pub fn texts(&self) -> Vec<String> {
+        let root_node = match self.text_system().root_node {
+            Some(root_node) => root_node,
+            None => return vec![],
+        };
+        let world = &self.app.world;
+        let mut texts = vec![];
+        let mut stack = vec![root_node];
+        while let Some(entity) = stack.pop() {
+            if let Some(text) = world.get::<Text>(entity) {
+                texts.push(text.sections.iter().map(|s| s.value.as_str()).collect());
+            }
+            if let Some(children) = world.get::<Children>(entity) {
+                stack.extend(children.iter().rev().copied());
+            }
+        }
+        texts
+    }
+
+    #[cfg(test)]
+    /// Render the entity tree currently spawned into text, with the name, the style
+    /// fields differing from the default, and the text of each entity, for comparison
+    /// with a snapshot.
+    pub fn snapshot(&self) -> String {
+        let mut snapshot = String::new();
+        if let Some(root_node) = self.text_system().root_node {
+            write_entity(&mut snapshot, &self.app.world, root_node, 0);
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+/// Write the style fields of a node which differ from the default style.
+fn write_style(out: &mut String, style: &Style, indent: &str) {
+    let default = Style::default();
+    macro_rules! fields {
+        ($($field:ident),*) => {
+            $(
+                if style.$field != default.$field {
+                    writeln!(out, "{}{}: {:?}", indent, stringify!($field), style.$field).unwrap();
+                }
+            )*
+        };
+    }
+    fields!(
+        display,
+        position_type,
+        direction,
+        flex_direction,
+        flex_wrap,
+        align_items,
+        align_self,
+        align_content,
+        justify_content,
+        position,
+        margin,
+        padding,
+        border,
+        flex_grow,
+        flex_shrink,
+        flex_basis,
+        size,
+        min_size,
+        max_size,
+        aspect_ratio,
+        overflow
+    );
+}
+
+#[cfg(test)]
+/// Write an entity and its children, recursively.
+fn write_entity(out: &mut String, world: &World, entity: Entity, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let name = world.get::<Name>(entity).map_or("-", |name| name.as_str());
+    writeln!(out, "{}{}", indent, name).unwrap();
+    let indent = format!("{}  | ", indent);
+    if let Some(style) = world.get::<Style>(entity) {
+        write_style(out, style, &indent);
+    }
+    if let Some(visibility) = world.get::<Visibility>(entity) {
+        if !visibility.is_visible {
+            writeln!(out, "{}hidden", indent).unwrap();
+        }
+    }
+    if let Some(text) = world.get::<Text>(entity) {
+        for section in &text.sections {
+            writeln!(
+                out,
+                "{}text: {:?} size {} color {:?}",
+                indent, section.value, section.style.font_size, section.style.color
+            )
+            .unwrap();
+        }
+    }
+    if let Some(children) = world.get::<Children>(entity) {
+        for child in children.iter() {
+            write_entity(out, world, *child, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Compare a snapshot with the file `src/snapshots/<name>.txt`, which must exist, or
+    /// write that file if the `UPDATE_SNAPSHOTS` environment variable is set.
+    fn assert_snapshot(name: &str, snapshot: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/snapshots")
+            .join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, snapshot).unwrap();
+            return;
+        }
+        if !path.exists() {
+            panic!(
+                "Missing snapshot '{}', run with UPDATE_SNAPSHOTS=1 to create it.",
+                name
+            );
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        if expected != snapshot {
+            let line = expected
+                .lines()
+                .zip(snapshot.lines())
+                .position(|(expected, actual)| expected != actual)
+                .unwrap_or_else(|| expected.lines().count().min(snapshot.lines().count()));
+            panic!(
+                "Snapshot '{}' differs from line {}, run with UPDATE_SNAPSHOTS=1 to accept \
+                 the changes.\n--- expected\n{}\n--- actual\n{}",
+                name,
+                line + 1,
+                expected.lines().nth(line).unwrap_or("<end>"),
+                snapshot.lines().nth(line).unwrap_or("<end>")
+            );
+        }
+    }
+
+    const BOOK: &str = r#"
+# Title
+A title
+[Start](#left)
+# Left
+Go left or right?
+[Left](#left) [Right](#right)
+# Right
+The end.
+"#;
+
+    #[test]
+    fn default_book_shortest_run() {
+        let mut playthrough = Playthrough::from_asset("text.json");
+        assert_eq!(playthrough.page_key().as_deref(), Some("0"));
+        let pages = playthrough.play(&["space", "space"]);
+        assert_eq!(
+            pages,
+            vec![Some("1".to_string()), Some("end_short".to_string())]
+        );
+        assert_eq!(playthrough.play(&["space"]), vec![None]);
+        assert_eq!(playthrough.page_read(), 3);
+    }
+
+    #[test]
+    fn default_book_longest_run() {
+        let mut playthrough = Playthrough::from_asset("text.json");
+        let keys = [
+            "space", "m", "y", "space", "space", "space", "2", "space", "space",
+        ];
+        let pages = playthrough.play(&keys);
+        let expected = [
+            "1",
+            "2",
+            "family1",
+            "family2",
+            "jam1",
+            "jam2",
+            "jam3_right",
+            "jam4",
+            "end",
+        ];
+        let expected: Vec<_> = expected.iter().map(|key| Some(key.to_string())).collect();
+        assert_eq!(pages, expected);
+        playthrough.press_button("space");
+        assert!(playthrough.is_scoreboard());
+        assert_eq!(playthrough.page_read(), 10);
+    }
+
+    #[test]
+    fn spawns_lines_and_buttons() {
+        let mut playthrough = Playthrough::new(BOOK, "md");
+        assert_eq!(playthrough.texts(), vec!["A title", "Start", "▲", "▼"]);
+        playthrough.press_button("space");
+        let texts = playthrough.texts();
+        assert_eq!(texts, vec!["Go left or right?", "Left", "Right", "▲", "▼"]);
+    }
+
+    #[test]
+    fn ignores_keys_without_button() {
+        let mut playthrough = Playthrough::new(BOOK, "md");
+        playthrough.play(&["1", "y"]);
+        assert_eq!(playthrough.page_key().as_deref(), Some("title"));
+        assert_eq!(playthrough.page_read(), 0);
+    }
+
+    #[test]
+    fn loops_and_restarts() {
+        let mut playthrough = Playthrough::new(BOOK, "md");
+        let pages = playthrough.play(&["space", "1", "1", "2", "space"]);
+        let expected = [
+            Some("left"),
+            Some("left"),
+            Some("left"),
+            Some("right"),
+            None,
+        ];
+        let expected: Vec<_> = expected.iter().map(|key| key.map(str::to_string)).collect();
+        assert_eq!(pages, expected);
+        assert_eq!(playthrough.page_read(), 5);
+
+        playthrough.press_button("space");
+        assert_eq!(playthrough.page_key().as_deref(), Some("title"));
+        assert_eq!(playthrough.page_read(), 0);
+    }
+
+    #[test]
+    fn snapshot_default_book() {
+        let mut playthrough = Playthrough::from_asset("text.json");
+        assert_snapshot("default_title", &playthrough.snapshot());
+        playthrough.play(&["space", "m", "y", "space", "space", "space"]);
+        assert_snapshot("default_choice", &playthrough.snapshot());
+        playthrough.play(&["1", "space", "space"]);
+        assert_snapshot("default_scoreboard", &playthrough.snapshot());
+    }
+
+    #[test]
+    fn snapshot_paginated_book() {
+        let mut source = "---\noverflow: Paginate\ntext_width: 600\n---\n# Long\n".to_string();
+        for index in 0..12 {
+            source.push_str(&format!(
+                "Line {} of a page too long for the screen.\n",
+                index
+            ));
+        }
+        let mut playthrough = Playthrough::new(&source, "md");
+        assert_snapshot("paginated_first_screen", &playthrough.snapshot());
+        playthrough.press_button("space");
+        assert_snapshot("paginated_second_screen", &playthrough.snapshot());
+    }
+
+    #[test]
+    fn next_page_past_the_last_page() {
+        let source = r#"{
+            "default_buttons": { "space": { "text": "Continue", "action": "NextPage" } },
+            "pages": [ { "lines": [ { "text": "First" } ] }, { "lines": [ { "text": "Last" } ] } ]
+        }"#;
+        let mut playthrough = Playthrough::new(source, "json");
+        assert_eq!(
+            playthrough.play(&["space", "space"]),
+            vec![Some("1".to_string()), None]
+        );
+        assert_eq!(playthrough.page_read(), 2);
+
+        let source = source.replace("\"pages\"", "\"end_of_book\": \"Wrap\", \"pages\"");
+        let mut playthrough = Playthrough::new(&source, "json");
+        let pages = playthrough.play(&["space", "space"]);
+        assert_eq!(pages, vec![Some("1".to_string()), Some("0".to_string())]);
+        assert_eq!(playthrough.page_read(), 2);
+    }
+
+    #[test]
+    fn reuses_unchanged_nodes() {
+        let source = r#"{
+            "default_buttons": { "space": { "text": "Continue", "action": "NextPage" } },
+            "pages": [
+                { "lines": [ { "text": "One" } ] },
+                { "lines": [ { "text": "Two" } ] }
+            ]
+        }"#;
+        let mut playthrough = Playthrough::new(source, "json");
+        let named = |playthrough: &mut Playthrough, name: &str| -> Vec<Entity> {
+            let world = &mut playthrough.app.world;
+            let mut query = world.query::<(Entity, &Name)>();
+            query
+                .iter(world)
+                .filter(|(_, entity_name)| entity_name.as_str() == name)
+                .map(|(entity, _)| entity)
+                .collect()
+        };
+        let background = named(&mut playthrough, "Background");
+        let button = named(&mut playthrough, "button:Continue");
+        let line = named(&mut playthrough, "Line0");
+        assert_eq!(button.len(), 1);
+
+        playthrough.press_button("space");
+        assert_eq!(playthrough.texts(), vec!["Two", "Continue", "▲", "▼"]);
+        assert_eq!(named(&mut playthrough, "Background"), background);
+        assert_eq!(named(&mut playthrough, "button:Continue"), button);
+        let new_line = named(&mut playthrough, "Line0");
+        assert_eq!(new_line.len(), 1);
+        assert_ne!(new_line, line);
+    }
+
+    #[test]
+    fn invalid_book_stays_loading() {
+        let mut playthrough = Playthrough::new(r#"{ "pages": [ { "lines": 3 } ] }"#, "json");
+        assert_eq!(playthrough.state(), GameState::Loading);
+        assert!(playthrough.book().is_none());
+        assert!(playthrough.load_error().is_some());
+        assert!(playthrough.texts().is_empty());
+
+        playthrough.press_button("space");
+        assert_eq!(playthrough.state(), GameState::Loading);
+        assert_eq!(playthrough.page_key(), None);
+    }
+
+    #[test]
+    fn pauses_and_opens_settings() {
+        let mut playthrough = Playthrough::from_asset("text.json");
+        assert_eq!(playthrough.state(), GameState::Title);
+        let has_text = |playthrough: &Playthrough, text: &str| {
+            playthrough.texts().iter().any(|value| value == text)
+        };
+
+        // Buttons are ignored while paused
+        playthrough.press(KeyCode::P);
+        assert_eq!(playthrough.state(), GameState::Paused);
+        assert!(has_text(&playthrough, "Paused"));
+        playthrough.press_button("space");
+        assert_eq!(playthrough.page_key(), Some("0".to_string()));
+
+        playthrough.press(KeyCode::S);
+        assert_eq!(playthrough.state(), GameState::Settings);
+        assert!(has_text(&playthrough, "Font size: 100% (+ / -)"));
+        playthrough.press(KeyCode::Equals);
+        assert!(has_text(&playthrough, "Font size: 110% (+ / -)"));
+
+        playthrough.press(KeyCode::S);
+        assert_eq!(playthrough.state(), GameState::Paused);
+        playthrough.press(KeyCode::P);
+        assert_eq!(playthrough.state(), GameState::Title);
+        assert!(!has_text(&playthrough, "Paused"));
+
+        assert_eq!(playthrough.play(&["space"]), vec![Some("1".to_string())]);
+        assert_eq!(playthrough.state(), GameState::Reading);
+        playthrough.play(&["space", "space"]);
+        assert_eq!(playthrough.state(), GameState::Scoreboard);
+        playthrough.press(KeyCode::P);
+        assert!(playthrough.is_scoreboard());
+        playthrough.press(KeyCode::P);
+        playthrough.press_button("space");
+        assert_eq!(playthrough.state(), GameState::Title);
+    }
+
+    #[test]
+    fn handles_keys_pressed_together() {
+        let mut playthrough = Playthrough::from_asset("text.json");
+        // Moving to the next page wins over the pause menu
+        playthrough.press_together(&[KeyCode::Space, KeyCode::P, KeyCode::S]);
+        assert_eq!(playthrough.state(), GameState::Reading);
+        assert_eq!(playthrough.page_key(), Some("1".to_string()));
+
+        playthrough.press(KeyCode::P);
+        playthrough.press_together(&[KeyCode::S, KeyCode::P]);
+        assert_eq!(playthrough.state(), GameState::Reading);
+        playthrough.press(KeyCode::P);
+        playthrough.press(KeyCode::S);
+        // Each key closes its menu
+        playthrough.press_together(&[KeyCode::S, KeyCode::P]);
+        assert_eq!(playthrough.state(), GameState::Reading);
+        assert_eq!(playthrough.page_key(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn sends_story_events() {
+        let mut playthrough = Playthrough::from_asset("text.json");
+        assert_eq!(playthrough.take_events::<crate::BookLoaded>().len(), 1);
+        let entered = |playthrough: &mut Playthrough| -> Vec<String> {
+            let events = playthrough.take_events::<crate::PageEntered>();
+            events.into_iter().map(|event| event.page_key).collect()
+        };
+        assert_eq!(entered(&mut playthrough), vec!["0"]);
+
+        playthrough.play(&["space", "space"]);
+        assert_eq!(entered(&mut playthrough), vec!["1", "end_short"]);
+        let choices: Vec<_> = playthrough
+            .take_events::<crate::ChoiceMade>()
+            .into_iter()
+            .map(|event| (event.page_key, event.button_key))
+            .collect();
+        let expected = [("0", "space"), ("1", "space")];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(page, button)| (page.to_string(), button.to_string()))
+            .collect();
+        assert_eq!(choices, expected);
+        let left = |playthrough: &mut Playthrough| -> Vec<String> {
+            let events = playthrough.take_events::<crate::PageLeft>();
+            events.into_iter().map(|event| event.page_key).collect()
+        };
+        assert_eq!(left(&mut playthrough), vec!["0", "1"]);
+
+        playthrough.press_button("space");
+        assert_eq!(left(&mut playthrough), vec!["end_short"]);
+        let finished = playthrough.take_events::<crate::RunFinished>();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].0.page_read, 3);
+        assert!(entered(&mut playthrough).is_empty());
+
+        // Restart
+        playthrough.press_button("space");
+        assert_eq!(entered(&mut playthrough), vec!["0"]);
+    }
+
+    #[test]
+    fn falls_back_from_fonts_failing_to_load() {
+        let source = r#"{
+            "fonts": { "typo": { "path": "fonts/typo.ttf" } },
+            "default_font": "typo",
+            "default_buttons": {},
+            "pages": [ { "lines": [ { "text": "Hello" } ] } ]
+        }"#;
+        let mut playthrough = Playthrough::new(source, "json");
+        let font = |playthrough: &mut Playthrough| -> Handle<Font> {
+            let mut query = playthrough.app.world.query::<&Text>();
+            let text = query
+                .iter(&playthrough.app.world)
+                .find(|text| text.sections[0].value == "Hello")
+                .unwrap();
+            text.sections[0].style.font.clone()
+        };
+        // The built-in font of the playthrough is the default handle
+        for _ in 0..LOAD_TIMEOUT_FRAMES {
+            if font(&mut playthrough) == Handle::default() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            playthrough.app.update();
+        }
+        assert_eq!(font(&mut playthrough), Handle::default());
+    }
+
+    #[test]
+    fn ignores_buttons_leading_nowhere() {
+        let source = r#"{
+            "default_buttons": {},
+            "pages": [
+                {
+                    "lines": [ { "text": "Start" } ],
+                    "buttons": {
+                        "1": { "text": "Lost", "action": { "JumpToPage": "nowhere" } },
+                        "space": { "text": "Go", "action": "NextPage" }
+                    }
+                },
+                { "lines": [ { "text": "End" } ], "is_final": true }
+            ]
+        }"#;
+        let mut playthrough = Playthrough::new(source, "json");
+        playthrough.take_events::<crate::PageEntered>();
+
+        playthrough.press_button("1");
+        assert_eq!(playthrough.page_key(), Some("0".to_string()));
+        assert_eq!(playthrough.page_read(), 0);
+        assert!(playthrough.take_events::<crate::ChoiceMade>().is_empty());
+        assert!(playthrough.take_events::<crate::PageLeft>().is_empty());
+        assert!(playthrough.take_events::<crate::PageEntered>().is_empty());
+
+        playthrough.press_button("space");
+        assert_eq!(playthrough.page_key(), Some("1".to_string()));
+        assert_eq!(playthrough.page_read(), 1);
+        assert_eq!(playthrough.take_events::<crate::PageLeft>().len(), 1);
+    }
+}
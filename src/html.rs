@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use std::fmt::Write;
+
+use crate::book::*;
+
+/// Identifier of the section of the end of the story, after the final pages.
+const END_ID: &str = "the-end";
+
+/// Escape a text for HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Convert a color into a CSS color value.
+fn css_color(color: Color) -> String {
+    let [red, green, blue, alpha] = color.as_rgba_f32();
+    format!(
+        "rgba({}, {}, {}, {})",
+        (red * 255.).round(),
+        (green * 255.).round(),
+        (blue * 255.).round(),
+        alpha
+    )
+}
+
+/// Convert a text alignment into a CSS `text-align` value.
+fn css_text_align(align: TextAlign) -> &'static str {
+    match align {
+        TextAlign::Start => "start",
+        TextAlign::Center => "center",
+        TextAlign::End => "end",
+        TextAlign::Justify => "justify",
+    }
+}
+
+/// Get the HTML identifier of the section of a page, escaped.
+///
+/// The page index keeps identifiers unique between keys only differing by the characters
+/// replaced, like "a b" and "a-b".
+fn page_id(book: &Book, page_index: usize) -> String {
+    let key: String = book
+        .page_key(page_index)
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    escape(&format!("page-{}-{}", page_index, key))
+}
+
+/// Get the HTML identifier of the section a button of a page leads to.
+fn target_id(book: &Book, page_index: usize, action: &ButtonAction) -> String {
+    match book.destination(page_index, action) {
+        Destination::Page(index) => page_id(book, index),
+        Destination::End | Destination::Missing => END_ID.to_string(),
+    }
+}
+
+/// Render a book into a self-contained HTML document.
+///
+/// Each page becomes a section with its background color, and each line a paragraph with
+/// its resolved style. Buttons become links to the section of the page they lead to, and
+/// final pages link to a closing section in place of the scoreboard. Texts are in the
+/// source language, and fonts fall back to the browser's.
+pub fn export(book: &Book) -> String {
+    let title = book
+        .pages
+        .first()
+        .and_then(|page| page.lines.first())
+        .map_or("Book", |line| line.text.as_str());
+    let base_style = book.base_style();
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(
+        html,
+        "<html lang=\"{}\" dir=\"{}\">",
+        escape(&book.source_language.code),
+        match book.source_language.direction {
+            TextDirection::LeftToRight => "ltr",
+            TextDirection::RightToLeft => "rtl",
+        }
+    )
+    .unwrap();
+    writeln!(html, "<head>").unwrap();
+    writeln!(html, "<meta charset=\"utf-8\">").unwrap();
+    writeln!(html, "<title>{}</title>", escape(title)).unwrap();
+    writeln!(html, "<style>").unwrap();
+    writeln!(
+        html,
+        "body {{ margin: 0; font-family: sans-serif; background: {}; }}",
+        css_color(base_style.background_color)
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "section {{ min-height: 100vh; box-sizing: border-box; padding: 2em; display: flex; \
+         flex-direction: column; justify-content: center; }}"
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "p {{ margin: {}px auto; max-width: {}; white-space: pre-wrap; }}",
+        book.line_spacing,
+        book.text_width
+            .map_or_else(|| "none".to_string(), |width| format!("{}px", width))
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "nav a {{ display: block; margin: 0.5em auto; text-align: center; }}"
+    )
+    .unwrap();
+    writeln!(html, "</style>").unwrap();
+    writeln!(html, "</head>").unwrap();
+    writeln!(html, "<body>").unwrap();
+
+    for (page_index, page) in book.pages.iter().enumerate() {
+        let page_style = book.page_style(page);
+        writeln!(
+            html,
+            "<section id=\"{}\" style=\"background: {};\">",
+            page_id(book, page_index),
+            css_color(page_style.background_color)
+        )
+        .unwrap();
+        for line in &page.lines {
+            let style = book.line_style(page, line);
+            writeln!(
+                html,
+                "<p style=\"color: {}; font-size: {}px; text-align: {};{}{}\">{}</p>",
+                css_color(style.color),
+                style.size,
+                css_text_align(style.align),
+                if style.bold {
+                    " font-weight: bold;"
+                } else {
+                    ""
+                },
+                if style.italic {
+                    " font-style: italic;"
+                } else {
+                    ""
+                },
+                escape(&line.text)
+            )
+            .unwrap();
+        }
+
+        let buttons = page.buttons.as_ref().unwrap_or(&book.default_buttons);
+        let mut buttons: Vec<_> = buttons.iter().collect();
+        buttons.sort_by(|a, b| a.0.cmp(b.0));
+        writeln!(html, "<nav>").unwrap();
+        for (key, button) in buttons {
+            let style = book.button_style(page, button);
+            writeln!(
+                html,
+                "<a href=\"#{}\" style=\"color: {};\">[{}] {}</a>",
+                target_id(book, page_index, &button.action),
+                css_color(style.color),
+                escape(key),
+                escape(&button.text)
+            )
+            .unwrap();
+        }
+        writeln!(html, "</nav>").unwrap();
+        writeln!(html, "</section>").unwrap();
+    }
+
+    let scoreboard_style = book.scoreboard_style();
+    writeln!(
+        html,
+        "<section id=\"{}\" style=\"background: {}; color: {};\">",
+        END_ID,
+        css_color(scoreboard_style.background_color),
+        css_color(scoreboard_style.color)
+    )
+    .unwrap();
+    writeln!(html, "<p style=\"text-align: center;\">The End</p>").unwrap();
+    if !book.pages.is_empty() {
+        writeln!(
+            html,
+            "<nav><a href=\"#{}\">Restart</a></nav>",
+            page_id(book, 0)
+        )
+        .unwrap();
+    }
+    writeln!(html, "</section>").unwrap();
+    writeln!(html, "</body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_page_ids_unique() {
+        let source = r#"{
+            "default_buttons": {},
+            "pages": [
+                {
+                    "name": "a b",
+                    "lines": [ { "text": "First" } ],
+                    "buttons": { "1": { "text": "Go", "action": { "JumpToPage": "a-b" } } }
+                },
+                { "name": "a-b", "lines": [ { "text": "Second" } ], "is_final": true }
+            ]
+        }"#;
+        let book = Book::parse(source, BookFormat::Json).unwrap();
+        assert_eq!(page_id(&book, 0), "page-0-a-b");
+        assert_eq!(page_id(&book, 1), "page-1-a-b");
+
+        let html = export(&book);
+        assert!(html.contains("<section id=\"page-0-a-b\""));
+        assert!(html.contains("<section id=\"page-1-a-b\""));
+        assert!(html.contains("<a href=\"#page-1-a-b\""));
+        assert!(html.contains("<a href=\"#page-0-a-b\">Restart</a>"));
+    }
+}
@@ -0,0 +1,1186 @@
+use serde_json::Value as Json;
+use std::collections::HashMap;
+
+/// Maximum number of steps to run before giving up, to catch infinite loops.
+const MAX_STEPS: usize = 1_000_000;
+
+/// Runtime value of an Ink expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    Str(String),
+    DivertTarget(Pointer),
+    Void,
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Int(v) => *v != 0,
+            Value::Float(v) => *v != 0.,
+            Value::Bool(v) => *v,
+            Value::Str(v) => !v.is_empty(),
+            Value::DivertTarget(_) => true,
+            Value::Void => false,
+        }
+    }
+
+    fn to_float(&self) -> Option<f32> {
+        match self {
+            Value::Int(v) => Some(*v as f32),
+            Value::Float(v) => Some(*v),
+            Value::Bool(v) => Some(if *v { 1. } else { 0. }),
+            _ => None,
+        }
+    }
+
+    fn to_int(&self) -> Option<i32> {
+        match self {
+            Value::Int(v) => Some(*v),
+            Value::Float(v) => Some(*v as i32),
+            Value::Bool(v) => Some(*v as i32),
+            _ => None,
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Value::Int(v) => v.to_string(),
+            Value::Float(v) => v.to_string(),
+            Value::Bool(v) => v.to_string(),
+            Value::Str(v) => v.clone(),
+            Value::DivertTarget(_) | Value::Void => String::new(),
+        }
+    }
+}
+
+/// Position of a node inside a container.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Pointer {
+    container: usize,
+    index: usize,
+}
+
+/// Target of a divert.
+#[derive(Clone, Debug)]
+enum Target {
+    Path(String),
+    Variable(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DivertKind {
+    Plain,
+    Function,
+    Tunnel,
+}
+
+/// Single node of compiled Ink content.
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Glue,
+    Command(String),
+    Native(String),
+    Literal(Value),
+    DivertTarget(String),
+    Divert {
+        target: Target,
+        kind: DivertKind,
+        conditional: bool,
+    },
+    Assign {
+        name: String,
+        global: bool,
+    },
+    VarRef(String),
+    ReadCount(String),
+    ChoicePoint {
+        path: String,
+        flags: u32,
+    },
+    Tag(String),
+    Container(usize),
+}
+
+/// Container of nodes, like a knot, a stitch, or a generated block.
+struct Container {
+    content: Vec<Node>,
+    /// Named sub-containers, both in `content` and named-only.
+    named: HashMap<String, usize>,
+    parent: Option<usize>,
+    /// Index in the content of the parent, or `None` if named-only.
+    index_in_parent: Option<usize>,
+    name: Option<String>,
+    /// Count flags: visits (1), turns (2), count at start only (4).
+    flags: u32,
+}
+
+/// Control commands supported by the runtime.
+const COMMANDS: &[&str] = &[
+    "ev",
+    "/ev",
+    "out",
+    "pop",
+    "->->",
+    "~ret",
+    "du",
+    "str",
+    "/str",
+    "nop",
+    "choiceCnt",
+    "turns",
+    "turn",
+    "readc",
+    "visit",
+    "seq",
+    "rnd",
+    "srnd",
+    "done",
+    "end",
+    "#",
+    "/#",
+];
+
+/// Native functions supported by the runtime.
+const NATIVE_FUNCTIONS: &[&str] = &[
+    "+", "-", "/", "*", "%", "_", "==", ">", "<", ">=", "<=", "!=", "!", "&&", "||", "MIN", "MAX",
+    "POW", "FLOOR", "CEILING", "INT", "FLOAT", "?", "!?",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FrameKind {
+    Root,
+    Function,
+    Tunnel,
+}
+
+/// Frame of the call stack.
+#[derive(Clone, Debug)]
+struct Frame {
+    kind: FrameKind,
+    return_to: Option<Pointer>,
+    temps: HashMap<String, Value>,
+    /// Evaluation mode of the caller, restored on return from a function.
+    in_eval: bool,
+}
+
+impl Frame {
+    fn root() -> Self {
+        Frame {
+            kind: FrameKind::Root,
+            return_to: None,
+            temps: HashMap::new(),
+            in_eval: false,
+        }
+    }
+}
+
+/// Item of the output stream.
+#[derive(Clone, Debug)]
+enum Output {
+    Text(String),
+    Glue,
+    /// Start of a string being evaluated.
+    StrStart,
+    /// Start of a dynamic tag.
+    TagStart,
+}
+
+/// Choice offered to the player.
+#[derive(Clone, Debug)]
+pub struct Choice {
+    pub text: String,
+    target: Pointer,
+    frames: Vec<Frame>,
+    is_invisible_default: bool,
+}
+
+/// Runtime for compiled Ink stories, as produced by `inklecate` (`.ink.json`).
+///
+/// Supports text, glue, diverts, tunnels, functions, choices, global and temporary
+/// variables, visit and turn counts, sequences, and tags. Lists, threads, and external
+/// functions are not supported, and rejected when loading.
+pub struct InkStory {
+    containers: Vec<Container>,
+    globals: HashMap<String, Value>,
+    visits: HashMap<usize, i32>,
+    turn_visits: HashMap<usize, i32>,
+    turn: i32,
+    pointer: Option<Pointer>,
+    frames: Vec<Frame>,
+    eval: Vec<Value>,
+    output: Vec<Output>,
+    in_eval: bool,
+    choices: Vec<Choice>,
+    tags: Vec<String>,
+    /// Container of the last node run, to name the current position.
+    last_container: usize,
+    has_ended: bool,
+    seed: u32,
+}
+
+impl InkStory {
+    /// Load a compiled Ink story from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let json: Json = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        if json.get("inkVersion").and_then(Json::as_u64).is_none() {
+            return Err("Missing inkVersion".to_string());
+        }
+        if let Some(lists) = json.get("listDefs").and_then(Json::as_object) {
+            if !lists.is_empty() {
+                return Err("Unsupported Ink feature: lists".to_string());
+            }
+        }
+        let root = json.get("root").ok_or("Missing root container")?;
+        let mut story = InkStory {
+            containers: vec![],
+            globals: HashMap::new(),
+            visits: HashMap::new(),
+            turn_visits: HashMap::new(),
+            turn: 0,
+            pointer: None,
+            frames: vec![],
+            eval: vec![],
+            output: vec![],
+            in_eval: false,
+            choices: vec![],
+            tags: vec![],
+            last_container: 0,
+            has_ended: false,
+            seed: 0,
+        };
+        story.parse_container(root, None, None, None)?;
+        story.reset()?;
+        Ok(story)
+    }
+
+    /// Parse a container and its sub-containers, returning its index.
+    fn parse_container(
+        &mut self,
+        json: &Json,
+        parent: Option<usize>,
+        index_in_parent: Option<usize>,
+        name: Option<String>,
+    ) -> Result<usize, String> {
+        let array = json.as_array().ok_or("Container is not an array")?;
+        let id = self.containers.len();
+        self.containers.push(Container {
+            content: vec![],
+            named: HashMap::new(),
+            parent,
+            index_in_parent,
+            name,
+            flags: 0,
+        });
+        let (terminal, content) = array.split_last().ok_or("Empty container")?;
+        let mut nodes = Vec::with_capacity(content.len());
+        for (index, item) in content.iter().enumerate() {
+            let node = if item.is_array() {
+                let child_name = item
+                    .as_array()
+                    .and_then(|array| array.last())
+                    .and_then(|terminal| terminal.get("#n"))
+                    .and_then(Json::as_str)
+                    .map(str::to_string);
+                let child =
+                    self.parse_container(item, Some(id), Some(index), child_name.clone())?;
+                if let Some(child_name) = child_name {
+                    self.containers[id].named.insert(child_name, child);
+                }
+                Node::Container(child)
+            } else {
+                parse_node(item)?
+            };
+            nodes.push(node);
+        }
+        self.containers[id].content = nodes;
+        if let Some(terminal) = terminal.as_object() {
+            for (key, value) in terminal {
+                match key.as_str() {
+                    "#f" => self.containers[id].flags = value.as_u64().unwrap_or(0) as u32,
+                    "#n" => {}
+                    _ => {
+                        let child =
+                            self.parse_container(value, Some(id), None, Some(key.clone()))?;
+                        self.containers[id].named.insert(key.clone(), child);
+                    }
+                }
+            }
+        }
+        Ok(id)
+    }
+
+    /// Restart the story from its beginning, resetting all its state.
+    pub fn reset(&mut self) -> Result<(), String> {
+        self.globals.clear();
+        self.visits.clear();
+        self.turn_visits.clear();
+        self.turn = 0;
+        self.frames = vec![Frame::root()];
+        self.eval.clear();
+        self.output.clear();
+        self.in_eval = false;
+        self.choices.clear();
+        self.tags.clear();
+        self.has_ended = false;
+        self.seed = 0;
+
+        // Initialize global variables
+        if let Some(&decl) = self.containers[0].named.get("global decl") {
+            self.pointer = Some(Pointer {
+                container: decl,
+                index: 0,
+            });
+            self.run()?;
+            self.output.clear();
+            self.has_ended = false;
+        }
+
+        self.frames = vec![Frame::root()];
+        self.pointer = Some(Pointer {
+            container: 0,
+            index: 0,
+        });
+        Ok(())
+    }
+
+    /// Run the story until it needs a choice from the player or ends, returning the text
+    /// produced, as rows separated by `\n`.
+    pub fn continue_maximally(&mut self) -> Result<String, String> {
+        self.tags.clear();
+        loop {
+            self.run()?;
+            // Follow the invisible default choice, if the player has no other choice
+            let is_choice_visible = self.choices.iter().any(|c| !c.is_invisible_default);
+            if is_choice_visible || self.has_ended {
+                break;
+            }
+            match self.choices.iter().position(|c| c.is_invisible_default) {
+                Some(index) => self.choose(index)?,
+                None => break,
+            }
+        }
+        let text = self.take_output();
+        if self.choices.is_empty() {
+            self.has_ended = true;
+        }
+        Ok(text)
+    }
+
+    /// Get the choices offered to the player, if any.
+    pub fn choices(&self) -> impl Iterator<Item = &Choice> {
+        self.choices.iter().filter(|c| !c.is_invisible_default)
+    }
+
+    /// Get the tags of the content produced by the last call to
+    /// [`InkStory::continue_maximally`].
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Has the story ended?
+    pub fn has_ended(&self) -> bool {
+        self.has_ended
+    }
+
+    /// Get the value of a global variable, as text.
+    pub fn variable(&self, name: &str) -> Option<String> {
+        self.globals.get(name).map(Value::to_text)
+    }
+
+    /// Get the path of the knot and stitch of the content produced last, like
+    /// `"knot.stitch"`.
+    pub fn current_path(&self) -> String {
+        let mut names = vec![];
+        let mut id = Some(self.last_container);
+        while let Some(container_id) = id {
+            let container = &self.containers[container_id];
+            if container.index_in_parent.is_none() {
+                if let Some(name) = &container.name {
+                    if !is_generated_name(name) {
+                        names.push(name.as_str());
+                    }
+                }
+            }
+            id = container.parent;
+        }
+        names.reverse();
+        names.join(".")
+    }
+
+    /// Choose one of the choices returned by [`InkStory::choices`].
+    pub fn choose_visible(&mut self, index: usize) -> Result<(), String> {
+        let index = self
+            .choices
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.is_invisible_default)
+            .nth(index)
+            .map(|(index, _)| index)
+            .ok_or("Invalid choice index")?;
+        self.choose(index)
+    }
+
+    fn choose(&mut self, index: usize) -> Result<(), String> {
+        let choice = self.choices[index].clone();
+        self.choices.clear();
+        self.frames = choice.frames;
+        self.eval.clear();
+        self.in_eval = false;
+        self.turn += 1;
+        self.divert_to(choice.target, None);
+        Ok(())
+    }
+
+    /// Collect the text of the output stream, applying glue.
+    fn take_output(&mut self) -> String {
+        let mut text = String::new();
+        let mut is_glued = false;
+        for item in self.output.drain(..) {
+            match item {
+                Output::Text(s) if s == "\n" => {
+                    if !is_glued && !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                }
+                Output::Text(s) => {
+                    if !s.trim().is_empty() {
+                        is_glued = false;
+                    }
+                    text.push_str(&s);
+                }
+                Output::Glue => {
+                    while text.ends_with('\n') {
+                        text.pop();
+                    }
+                    is_glued = true;
+                }
+                Output::StrStart | Output::TagStart => {}
+            }
+        }
+        text.lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn pop(&mut self) -> Result<Value, String> {
+        self.eval
+            .pop()
+            .ok_or_else(|| "Evaluation stack underflow".to_string())
+    }
+
+    fn pop_int(&mut self) -> Result<i32, String> {
+        let value = self.pop()?;
+        value
+            .to_int()
+            .ok_or_else(|| format!("Expected a number, got {:?}", value))
+    }
+
+    fn pop_target(&mut self) -> Result<Pointer, String> {
+        match self.pop()? {
+            Value::DivertTarget(pointer) => Ok(pointer),
+            value => Err(format!("Expected a divert target, got {:?}", value)),
+        }
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn variable_value(&self, name: &str) -> Result<Value, String> {
+        self.frames
+            .last()
+            .and_then(|frame| frame.temps.get(name))
+            .or_else(|| self.globals.get(name))
+            .cloned()
+            .ok_or_else(|| format!("Unknown variable '{}'", name))
+    }
+
+    /// Pseudo-random number generator, deterministic for a given seed.
+    fn next_random(&mut self) -> u32 {
+        self.seed = self.seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (self.seed >> 16) & 0x7fff
+    }
+
+    /// Resolve a path, absolute or relative to the node at `from`.
+    fn resolve(&self, path: &str, from: Pointer) -> Result<Pointer, String> {
+        // The first component of a relative path designates the container of the node
+        // itself, and not its parent.
+        let (mut container, components) = match path.strip_prefix(".^") {
+            Some(relative) => (from.container, relative.trim_start_matches('.')),
+            None => (0, path),
+        };
+        let mut index = None;
+        for component in components.split('.').filter(|c| !c.is_empty()) {
+            if index.is_some() {
+                return Err(format!("Invalid path '{}'", path));
+            }
+            if component == "^" {
+                container = self.containers[container]
+                    .parent
+                    .ok_or_else(|| format!("Invalid path '{}'", path))?;
+            } else if let Ok(i) = component.parse::<usize>() {
+                match self.containers[container].content.get(i) {
+                    Some(Node::Container(child)) => container = *child,
+                    Some(_) => index = Some(i),
+                    None => return Err(format!("Invalid path '{}'", path)),
+                }
+            } else {
+                container = *self.containers[container]
+                    .named
+                    .get(component)
+                    .ok_or_else(|| format!("Invalid path '{}'", path))?;
+            }
+        }
+        Ok(Pointer {
+            container,
+            index: index.unwrap_or(0),
+        })
+    }
+
+    /// Record a visit of a container.
+    fn visit(&mut self, container: usize, at_start: bool) {
+        let flags = self.containers[container].flags;
+        if flags & 4 != 0 && !at_start {
+            return;
+        }
+        if flags & 1 != 0 {
+            *self.visits.entry(container).or_default() += 1;
+        }
+        if flags & 2 != 0 {
+            self.turn_visits.insert(container, self.turn);
+        }
+    }
+
+    /// Move to a new position, visiting the containers entered on the way.
+    fn divert_to(&mut self, target: Pointer, from: Option<Pointer>) {
+        let mut previous = vec![];
+        let mut id = from.map(|from| from.container);
+        while let Some(container) = id {
+            previous.push(container);
+            id = self.containers[container].parent;
+        }
+        let mut container = Some(target.container);
+        let mut at_start = target.index == 0;
+        while let Some(id) = container {
+            let counts_at_start_only = self.containers[id].flags & 4 != 0;
+            if previous.contains(&id) && !counts_at_start_only {
+                break;
+            }
+            self.visit(id, at_start);
+            at_start = at_start && self.containers[id].index_in_parent == Some(0);
+            container = self.containers[id].parent;
+        }
+        self.pointer = Some(target);
+    }
+
+    /// Run nodes until the flow pauses or ends.
+    fn run(&mut self) -> Result<(), String> {
+        for _ in 0..MAX_STEPS {
+            let pointer = match self.pointer {
+                Some(pointer) => pointer,
+                None => {
+                    // Running out of content inside a function returns from it, without
+                    // any value
+                    if self.frames.len() > 1 && self.frame().kind == FrameKind::Function {
+                        self.return_from_function();
+                        if self.in_eval {
+                            self.eval.push(Value::Void);
+                        }
+                        continue;
+                    }
+                    return Ok(());
+                }
+            };
+            let container = &self.containers[pointer.container];
+            if pointer.index >= container.content.len() {
+                // End of container, continue after it in its parent
+                self.pointer = match (container.parent, container.index_in_parent) {
+                    (Some(parent), Some(index)) => Some(Pointer {
+                        container: parent,
+                        index: index + 1,
+                    }),
+                    _ => None,
+                };
+                continue;
+            }
+            let node = container.content[pointer.index].clone();
+            self.last_container = pointer.container;
+            self.pointer = Some(Pointer {
+                container: pointer.container,
+                index: pointer.index + 1,
+            });
+            if !self.step(node, pointer)? {
+                return Ok(());
+            }
+        }
+        Err("Too many steps, the story may contain an infinite loop".to_string())
+    }
+
+    /// Return from a function, leaving its return value, if any, on the evaluation stack.
+    fn return_from_function(&mut self) {
+        let frame = self.frames.pop().unwrap();
+        self.in_eval = frame.in_eval;
+        self.pointer = frame.return_to;
+    }
+
+    /// Run a single node. Returns `false` if the flow paused or ended.
+    fn step(&mut self, node: Node, pointer: Pointer) -> Result<bool, String> {
+        match node {
+            Node::Container(id) => {
+                self.visit(id, true);
+                self.pointer = Some(Pointer {
+                    container: id,
+                    index: 0,
+                });
+            }
+            Node::Text(text) => {
+                if self.in_eval {
+                    self.eval.push(Value::Str(text));
+                } else {
+                    self.output.push(Output::Text(text));
+                }
+            }
+            Node::Glue => self.output.push(Output::Glue),
+            Node::Literal(value) => {
+                if self.in_eval {
+                    self.eval.push(value);
+                } else {
+                    self.output.push(Output::Text(value.to_text()));
+                }
+            }
+            Node::DivertTarget(path) => {
+                let target = self.resolve(&path, pointer)?;
+                self.eval.push(Value::DivertTarget(target));
+            }
+            Node::Divert {
+                target,
+                kind,
+                conditional,
+            } => {
+                if conditional && !self.pop()?.is_truthy() {
+                    return Ok(true);
+                }
+                let target = match target {
+                    Target::Path(path) => self.resolve(&path, pointer)?,
+                    Target::Variable(name) => match self.variable_value(&name)? {
+                        Value::DivertTarget(target) => target,
+                        value => {
+                            return Err(format!("Variable '{}' is not a divert: {:?}", name, value))
+                        }
+                    },
+                };
+                if kind != DivertKind::Plain {
+                    self.frames.push(Frame {
+                        kind: if kind == DivertKind::Function {
+                            FrameKind::Function
+                        } else {
+                            FrameKind::Tunnel
+                        },
+                        return_to: self.pointer,
+                        temps: HashMap::new(),
+                        in_eval: self.in_eval,
+                    });
+                    if kind == DivertKind::Function {
+                        self.in_eval = false;
+                    }
+                }
+                self.divert_to(target, Some(pointer));
+            }
+            Node::Assign { name, global } => {
+                let value = self.pop()?;
+                if global {
+                    self.globals.insert(name, value);
+                } else {
+                    self.frame().temps.insert(name, value);
+                }
+            }
+            Node::VarRef(name) => {
+                let value = self.variable_value(&name)?;
+                self.eval.push(value);
+            }
+            Node::ReadCount(path) => {
+                let target = self.resolve(&path, pointer)?;
+                let count = self.visits.get(&target.container).copied().unwrap_or(0);
+                self.eval.push(Value::Int(count));
+            }
+            Node::ChoicePoint { path, flags } => {
+                let mut is_shown = true;
+                if flags & 1 != 0 && !self.pop()?.is_truthy() {
+                    is_shown = false;
+                }
+                let choice_only = if flags & 4 != 0 {
+                    self.pop()?.to_text()
+                } else {
+                    String::new()
+                };
+                let start = if flags & 2 != 0 {
+                    self.pop()?.to_text()
+                } else {
+                    String::new()
+                };
+                let target = self.resolve(&path, pointer)?;
+                if flags & 16 != 0 && self.visits.get(&target.container).copied().unwrap_or(0) > 0 {
+                    is_shown = false;
+                }
+                if is_shown {
+                    self.choices.push(Choice {
+                        text: format!("{}{}", start, choice_only).trim().to_string(),
+                        target,
+                        frames: self.frames.clone(),
+                        is_invisible_default: flags & 8 != 0,
+                    });
+                }
+            }
+            Node::Tag(tag) => self.tags.push(tag),
+            Node::Command(command) => return self.command(&command),
+            Node::Native(function) => self.native(&function)?,
+        }
+        Ok(true)
+    }
+
+    /// Run a control command. Returns `false` if the flow paused or ended.
+    fn command(&mut self, command: &str) -> Result<bool, String> {
+        match command {
+            "ev" => self.in_eval = true,
+            "/ev" => self.in_eval = false,
+            "out" => {
+                let value = self.pop()?;
+                self.output.push(Output::Text(value.to_text()));
+            }
+            "pop" => {
+                self.pop()?;
+            }
+            "du" => {
+                let value = self
+                    .eval
+                    .last()
+                    .cloned()
+                    .ok_or("Evaluation stack underflow")?;
+                self.eval.push(value);
+            }
+            "str" | "#" => {
+                self.output.push(if command == "str" {
+                    Output::StrStart
+                } else {
+                    Output::TagStart
+                });
+                self.in_eval = false;
+            }
+            "/str" | "/#" => {
+                let start = self
+                    .output
+                    .iter()
+                    .rposition(|item| {
+                        matches!(
+                            (item, command),
+                            (Output::StrStart, "/str") | (Output::TagStart, "/#")
+                        )
+                    })
+                    .ok_or("Unbalanced string or tag")?;
+                let text: String = self
+                    .output
+                    .drain(start..)
+                    .filter_map(|item| match item {
+                        Output::Text(text) => Some(text),
+                        _ => None,
+                    })
+                    .collect();
+                if command == "/str" {
+                    self.eval.push(Value::Str(text));
+                    self.in_eval = true;
+                } else {
+                    self.tags.push(text.trim().to_string());
+                }
+            }
+            "->->" => {
+                if self.frame().kind != FrameKind::Tunnel {
+                    return Err("Tunnel return outside of a tunnel".to_string());
+                }
+                // The compiler pushes a void value before returning from a tunnel
+                if self.eval.last() == Some(&Value::Void) {
+                    self.eval.pop();
+                }
+                let frame = self.frames.pop().unwrap();
+                self.pointer = frame.return_to;
+            }
+            "~ret" => {
+                if self.frame().kind != FrameKind::Function {
+                    return Err("Function return outside of a function".to_string());
+                }
+                self.return_from_function();
+            }
+            "nop" => {}
+            "choiceCnt" => self.eval.push(Value::Int(self.choices.len() as i32)),
+            "turns" => self.eval.push(Value::Int(self.turn)),
+            "turn" => {
+                let target = self.pop_target()?;
+                let turns = self
+                    .turn_visits
+                    .get(&target.container)
+                    .map_or(-1, |turn| self.turn - turn);
+                self.eval.push(Value::Int(turns));
+            }
+            "readc" => {
+                let target = self.pop_target()?;
+                let count = self.visits.get(&target.container).copied().unwrap_or(0);
+                self.eval.push(Value::Int(count));
+            }
+            "visit" => {
+                let container = self.last_container;
+                let count = self.visits.get(&container).copied().unwrap_or(0);
+                self.eval.push(Value::Int(count - 1));
+            }
+            "seq" => {
+                let element_count = self.pop_int()?.max(1);
+                let sequence_count = self.pop_int()?;
+                let loop_index = sequence_count / element_count;
+                let iteration = sequence_count % element_count;
+                // Shuffle the elements the same way for all iterations of a loop
+                let saved_seed = self.seed;
+                self.seed = (self.last_container as u32)
+                    .wrapping_mul(31)
+                    .wrapping_add(loop_index as u32);
+                let mut unpicked: Vec<i32> = (0..element_count).collect();
+                let mut picked = 0;
+                for _ in 0..=iteration {
+                    let index = self.next_random() as usize % unpicked.len();
+                    picked = unpicked.remove(index);
+                }
+                self.seed = saved_seed;
+                self.eval.push(Value::Int(picked));
+            }
+            "rnd" => {
+                let max = self.pop_int()?;
+                let min = self.pop_int()?;
+                let range = (i64::from(max) - i64::from(min) + 1).max(1);
+                let value = (i64::from(min) + i64::from(self.next_random()) % range) as i32;
+                self.eval.push(Value::Int(value));
+            }
+            "srnd" => {
+                self.seed = self.pop_int()? as u32;
+                self.eval.push(Value::Void);
+            }
+            "done" => {
+                self.pointer = None;
+                return Ok(false);
+            }
+            "end" => {
+                self.pointer = None;
+                self.has_ended = true;
+                return Ok(false);
+            }
+            _ => return Err(format!("Unsupported command '{}'", command)),
+        }
+        Ok(true)
+    }
+
+    /// Run a native function on the evaluation stack.
+    fn native(&mut self, function: &str) -> Result<(), String> {
+        let result = match function {
+            "_" | "!" | "FLOOR" | "CEILING" | "INT" | "FLOAT" => {
+                let value = self.pop()?;
+                match (function, &value) {
+                    ("!", value) => Value::Bool(!value.is_truthy()),
+                    ("_", Value::Int(v)) => Value::Int(v.wrapping_neg()),
+                    ("INT", value) => Value::Int(value.to_int().unwrap_or(0)),
+                    (_, value) => {
+                        let v = value
+                            .to_float()
+                            .ok_or_else(|| format!("Invalid operand for {}", function))?;
+                        match function {
+                            "_" => Value::Float(-v),
+                            "FLOOR" => Value::Float(v.floor()),
+                            "CEILING" => Value::Float(v.ceil()),
+                            _ => Value::Float(v),
+                        }
+                    }
+                }
+            }
+            _ => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                binary(function, a, b)?
+            }
+        };
+        self.eval.push(result);
+        Ok(())
+    }
+}
+
+/// Is a container name generated by the compiler, rather than a knot or stitch name?
+fn is_generated_name(name: &str) -> bool {
+    let is_numbered = |prefix: &str| {
+        name.strip_prefix(prefix)
+            .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+    };
+    name == "s"
+        || name.starts_with('$')
+        || name == "global decl"
+        || is_numbered("c-")
+        || is_numbered("g-")
+}
+
+/// Evaluate a binary native function.
+fn binary(function: &str, a: Value, b: Value) -> Result<Value, String> {
+    match function {
+        "&&" => return Ok(Value::Bool(a.is_truthy() && b.is_truthy())),
+        "||" => return Ok(Value::Bool(a.is_truthy() || b.is_truthy())),
+        _ => {}
+    }
+    if let (Value::Str(a), Value::Str(b)) = (&a, &b) {
+        return Ok(match function {
+            "+" => Value::Str(format!("{}{}", a, b)),
+            "==" => Value::Bool(a == b),
+            "!=" => Value::Bool(a != b),
+            "?" => Value::Bool(a.contains(b.as_str())),
+            "!?" => Value::Bool(!a.contains(b.as_str())),
+            _ => return Err(format!("Invalid operation {} on strings", function)),
+        });
+    }
+    if let (Value::DivertTarget(a), Value::DivertTarget(b)) = (&a, &b) {
+        return Ok(match function {
+            "==" => Value::Bool(a == b),
+            "!=" => Value::Bool(a != b),
+            _ => return Err(format!("Invalid operation {} on divert targets", function)),
+        });
+    }
+    if let (Value::Int(a), Value::Int(b)) = (&a, &b) {
+        let (a, b) = (*a, *b);
+        return Ok(match function {
+            "+" => Value::Int(a.wrapping_add(b)),
+            "-" => Value::Int(a.wrapping_sub(b)),
+            "*" => Value::Int(a.wrapping_mul(b)),
+            "/" if b == 0 => return Err("Division by zero".to_string()),
+            "/" => Value::Int(a.wrapping_div(b)),
+            "%" if b == 0 => return Err("Division by zero".to_string()),
+            "%" => Value::Int(a.wrapping_rem(b)),
+            "==" => Value::Bool(a == b),
+            "!=" => Value::Bool(a != b),
+            ">" => Value::Bool(a > b),
+            "<" => Value::Bool(a < b),
+            ">=" => Value::Bool(a >= b),
+            "<=" => Value::Bool(a <= b),
+            "MIN" => Value::Int(a.min(b)),
+            "MAX" => Value::Int(a.max(b)),
+            "POW" => Value::Float((a as f32).powf(b as f32)),
+            _ => return Err(format!("Invalid operation {} on numbers", function)),
+        });
+    }
+    let (a, b) = match (a.to_float(), b.to_float()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(format!("Invalid operands for {}", function)),
+    };
+    Ok(match function {
+        "+" => Value::Float(a + b),
+        "-" => Value::Float(a - b),
+        "*" => Value::Float(a * b),
+        "/" => Value::Float(a / b),
+        "%" => Value::Float(a % b),
+        "==" => Value::Bool(a == b),
+        "!=" => Value::Bool(a != b),
+        ">" => Value::Bool(a > b),
+        "<" => Value::Bool(a < b),
+        ">=" => Value::Bool(a >= b),
+        "<=" => Value::Bool(a <= b),
+        "MIN" => Value::Float(a.min(b)),
+        "MAX" => Value::Float(a.max(b)),
+        "POW" => Value::Float(a.powf(b)),
+        _ => return Err(format!("Invalid operation {} on numbers", function)),
+    })
+}
+
+/// Parse a single non-container node.
+fn parse_node(json: &Json) -> Result<Node, String> {
+    match json {
+        Json::String(s) => {
+            if let Some(text) = s.strip_prefix('^') {
+                Ok(Node::Text(text.to_string()))
+            } else if s == "\n" {
+                Ok(Node::Text(s.clone()))
+            } else if s == "<>" {
+                Ok(Node::Glue)
+            } else if s == "void" {
+                Ok(Node::Literal(Value::Void))
+            } else if COMMANDS.contains(&s.as_str()) {
+                Ok(Node::Command(s.clone()))
+            } else if NATIVE_FUNCTIONS.contains(&s.as_str()) {
+                Ok(Node::Native(s.clone()))
+            } else {
+                Err(format!("Unsupported Ink feature: '{}'", s))
+            }
+        }
+        Json::Number(n) => Ok(Node::Literal(match n.as_i64() {
+            Some(v) => Value::Int(v as i32),
+            None => Value::Float(n.as_f64().unwrap_or(0.) as f32),
+        })),
+        Json::Bool(b) => Ok(Node::Literal(Value::Bool(*b))),
+        Json::Object(object) => {
+            let get_str = |key: &str| object.get(key).and_then(Json::as_str).map(str::to_string);
+            let is_set = |key: &str| object.get(key).and_then(Json::as_bool).unwrap_or(false);
+            if let Some(path) = get_str("^->") {
+                Ok(Node::DivertTarget(path))
+            } else if let Some(target) = get_str("->") {
+                Ok(Node::Divert {
+                    target: if is_set("var") {
+                        Target::Variable(target)
+                    } else {
+                        Target::Path(target)
+                    },
+                    kind: DivertKind::Plain,
+                    conditional: is_set("c"),
+                })
+            } else if let Some(target) = get_str("f()") {
+                Ok(Node::Divert {
+                    target: Target::Path(target),
+                    kind: DivertKind::Function,
+                    conditional: is_set("c"),
+                })
+            } else if let Some(target) = get_str("->t->") {
+                Ok(Node::Divert {
+                    target: if is_set("var") {
+                        Target::Variable(target)
+                    } else {
+                        Target::Path(target)
+                    },
+                    kind: DivertKind::Tunnel,
+                    conditional: is_set("c"),
+                })
+            } else if let Some(name) = get_str("VAR=") {
+                Ok(Node::Assign { name, global: true })
+            } else if let Some(name) = get_str("temp=") {
+                Ok(Node::Assign {
+                    name,
+                    global: false,
+                })
+            } else if let Some(name) = get_str("VAR?") {
+                Ok(Node::VarRef(name))
+            } else if let Some(path) = get_str("CNT?") {
+                Ok(Node::ReadCount(path))
+            } else if let Some(path) = get_str("*") {
+                let flags = object.get("flg").and_then(Json::as_u64).unwrap_or(0) as u32;
+                Ok(Node::ChoicePoint { path, flags })
+            } else if let Some(tag) = get_str("#") {
+                Ok(Node::Tag(tag))
+            } else if object.contains_key("x()") {
+                Err("Unsupported Ink feature: external functions".to_string())
+            } else if object.contains_key("^var") {
+                Err("Unsupported Ink feature: variable references".to_string())
+            } else if object.contains_key("list") {
+                Err("Unsupported Ink feature: lists".to_string())
+            } else {
+                Err(format!("Unsupported Ink node: {}", json))
+            }
+        }
+        _ => Err(format!("Unsupported Ink node: {}", json)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choice_texts(story: &InkStory) -> Vec<&str> {
+        story.choices().map(|choice| choice.text.as_str()).collect()
+    }
+
+    #[test]
+    fn text_glue_and_diverts() {
+        let mut story = InkStory::from_json(include_str!("fixtures/ink/basics.ink.json")).unwrap();
+        assert_eq!(story.continue_maximally().unwrap(), "Hello world.");
+        assert_eq!(choice_texts(&story), vec!["Enter the forest"]);
+        assert!(!story.has_ended());
+
+        story.choose_visible(0).unwrap();
+        assert_eq!(
+            story.continue_maximally().unwrap(),
+            "Trees everywhere.\nA quiet clearing."
+        );
+        assert_eq!(story.current_path(), "forest.clearing");
+        assert!(story.choices().next().is_none());
+        assert!(story.has_ended());
+        assert!(story.choose_visible(0).is_err());
+
+        // Restart from the beginning
+        story.reset().unwrap();
+        assert_eq!(story.continue_maximally().unwrap(), "Hello world.");
+    }
+
+    #[test]
+    fn choices_variables_and_visit_counts() {
+        let source = include_str!("fixtures/ink/choices.ink.json");
+        let mut story = InkStory::from_json(source).unwrap();
+        assert_eq!(
+            story.continue_maximally().unwrap(),
+            "You have 0 gold. You came here 1 times."
+        );
+        // The conditional choice is hidden
+        assert_eq!(choice_texts(&story), vec!["Take the gold", "Leave"]);
+        assert_eq!(story.current_path(), "hub");
+
+        story.choose_visible(0).unwrap();
+        assert_eq!(
+            story.continue_maximally().unwrap(),
+            "You have 10 gold. You came here 2 times."
+        );
+        assert_eq!(story.variable("gold"), Some("10".to_string()));
+        // The once-only choice is gone, and the conditional one shown
+        assert_eq!(choice_texts(&story), vec!["Spend the gold", "Leave"]);
+
+        story.choose_visible(0).unwrap();
+        assert_eq!(story.continue_maximally().unwrap(), "The shop is closed.");
+        assert_eq!(story.current_path(), "shop");
+        assert!(story.has_ended());
+
+        // Resetting also resets the variables and visit counts
+        story.reset().unwrap();
+        assert_eq!(
+            story.continue_maximally().unwrap(),
+            "You have 0 gold. You came here 1 times."
+        );
+    }
+
+    #[test]
+    fn tunnels_and_functions() {
+        let source = include_str!("fixtures/ink/tunnel.ink.json");
+        let mut story = InkStory::from_json(source).unwrap();
+        assert_eq!(
+            story.continue_maximally().unwrap(),
+            "Before, inside the aside, after.\nThe answer is 42."
+        );
+        assert_eq!(story.current_path(), "start");
+        assert!(story.has_ended());
+    }
+
+    #[test]
+    fn wraps_integer_overflows() {
+        let overflows = r#"{"inkVersion":21,"root":[["ev",-2147483648,-1,"/","out","/ev","^ ","ev",-2147483648,-1,"%","out","/ev","^ ","ev",-2147483648,"_","out","/ev","^ ","ev",-2147483648,2147483647,"rnd","out","/ev","\n","done",null],"done",null],"listDefs":{}}"#;
+        let mut story = InkStory::from_json(overflows).unwrap();
+        let text = story.continue_maximally().unwrap();
+        let values: Vec<i32> = text.split(' ').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values[..3], [i32::MIN, 0, i32::MIN]);
+        assert_eq!(values.len(), 4);
+    }
+
+    #[test]
+    fn rejects_unsupported_features() {
+        let lists = r#"{"inkVersion":21,"root":[["done",null],"done",null],"listDefs":{"colors":{"red":1,"blue":2}}}"#;
+        assert_eq!(
+            InkStory::from_json(lists).err(),
+            Some("Unsupported Ink feature: lists".to_string())
+        );
+
+        let externals = r#"{"inkVersion":21,"root":[["ev",{"x()":"roll","exArgs":0},"out","/ev","\n","done",null],"done",null],"listDefs":{}}"#;
+        assert_eq!(
+            InkStory::from_json(externals).err(),
+            Some("Unsupported Ink feature: external functions".to_string())
+        );
+
+        assert!(InkStory::from_json(r#"{"root":[]}"#).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use ab_glyph::{Font as _, FontArc, ScaleFont};
+use bevy::prelude::*;
+use std::ops::Range;
+
+/// Measure the horizontal advance of a run of text, in logical pixels.
+pub fn measure_text(font: &FontArc, font_size: f32, text: &str) -> f32 {
+    let font = font.as_scaled(font_size);
+    let mut width = 0.;
+    let mut prev = None;
+    for c in text.chars() {
+        let glyph_id = font.glyph_id(c);
+        if let Some(prev) = prev {
+            width += font.kern(prev, glyph_id);
+        }
+        width += font.h_advance(glyph_id);
+        prev = Some(glyph_id);
+    }
+    width
+}
+
+/// Break a paragraph into rows of words fitting inside `max_width`, greedily.
+///
+/// A word wider than `max_width` on its own is placed on a row by itself rather than
+/// being split. The returned rows are never empty, except for an empty paragraph which
+/// produces a single empty row.
+pub fn wrap_words<'a>(
+    font: &FontArc,
+    font_size: f32,
+    paragraph: &'a str,
+    max_width: f32,
+) -> Vec<Vec<&'a str>> {
+    let space_width = measure_text(font, font_size, " ");
+    let mut rows = vec![];
+    let mut row: Vec<&str> = vec![];
+    let mut row_width = 0.;
+    for word in paragraph.split_whitespace() {
+        let word_width = measure_text(font, font_size, word);
+        if !row.is_empty() && row_width + space_width + word_width > max_width {
+            rows.push(std::mem::take(&mut row));
+            row_width = 0.;
+        }
+        if !row.is_empty() {
+            row_width += space_width;
+        }
+        row_width += word_width;
+        row.push(word);
+    }
+    rows.push(row);
+    rows
+}
+
+/// Get the font data of a font asset, if loaded.
+pub fn font_data<'a>(fonts: &'a Assets<Font>, handle: &Handle<Font>) -> Option<&'a FontArc> {
+    fonts.get(handle).map(|font| &font.font)
+}
+
+/// Height of a single row of text, as laid out by the text pipeline.
+pub fn row_height(font: &FontArc, font_size: f32) -> f32 {
+    let font = font.as_scaled(font_size);
+    font.ascent() - font.descent() + font.line_gap()
+}
+
+/// Estimate the height of a block of text, wrapped inside `max_width` if any.
+///
+/// Without font data, rows are estimated from an average glyph width.
+pub fn text_height(
+    font: Option<&FontArc>,
+    font_size: f32,
+    text: &str,
+    max_width: Option<f32>,
+) -> f32 {
+    let row_count: usize = text
+        .split('\n')
+        .map(|paragraph| match (font, max_width) {
+            (Some(font), Some(max_width)) => {
+                wrap_words(font, font_size, paragraph, max_width).len()
+            }
+            (None, Some(max_width)) => {
+                let width = paragraph.chars().count() as f32 * font_size * 0.6;
+                ((width / max_width).ceil() as usize).max(1)
+            }
+            (_, None) => 1,
+        })
+        .sum();
+    let row_height = font.map_or(font_size * 1.2, |font| row_height(font, font_size));
+    row_count as f32 * row_height
+}
+
+/// Split a sequence of items into consecutive ranges, each fitting inside `available`
+/// height, greedily.
+///
+/// Every range contains at least one item, even if that item alone doesn't fit. An empty
+/// sequence produces a single empty range.
+pub fn paginate(heights: &[f32], available: f32) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut height = 0.;
+    for (index, item_height) in heights.iter().enumerate() {
+        if index > start && height + item_height > available {
+            ranges.push(start..index);
+            start = index;
+            height = 0.;
+        }
+        height += item_height;
+    }
+    ranges.push(start..heights.len());
+    ranges
+}
@@ -0,0 +1,30 @@
+mod bidi;
+pub mod book;
+mod cli;
+pub mod color;
+mod events;
+mod fluent_asset;
+mod fonts;
+mod fuzz;
+mod graph;
+mod headless;
+mod html;
+mod ink;
+mod layout;
+mod localization;
+mod markdown;
+mod paths;
+mod plugin;
+mod scroll;
+mod state;
+mod stats;
+pub mod story;
+mod text_asset;
+mod text_system;
+mod twee;
+
+pub use cli::run as run_tool;
+pub use events::{BookLoaded, ChoiceMade, PageEntered, PageLeft, RunFinished};
+pub use plugin::{StoryAssets, StoryPlugin, StorySystem};
+pub use state::GameState;
+pub use text_system::{Score, TextSystem};
@@ -0,0 +1,461 @@
+use bevy::prelude::*;
+use fluent_bundle::{bundle::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use fluent_syntax::ast;
+use intl_memoizer::concurrent::IntlLangMemoizer;
+use serde::Deserialize;
+use std::{borrow::Cow, collections::HashMap, path::Path, sync::Arc};
+use unic_langid::LanguageIdentifier;
+
+use crate::book::{Book, Language, TextDirection};
+use crate::fluent_asset::FluentAsset;
+use crate::text_asset::TextAsset;
+
+/// Identifiers and source text of the strings of the user interface outside of the book
+/// pages.
+pub const UI_STRINGS: &[(&str, &str)] = &[
+    ("ui.continue", "Continue"),
+    ("ui.score", "Score"),
+    ("ui.pages_read", "{pages_read} pages read"),
+    ("ui.restart", "Restart"),
+    ("ui.paused", "Paused"),
+    ("ui.resume", "P: resume"),
+    ("ui.open_settings", "S: settings"),
+    ("ui.settings", "Settings"),
+    ("ui.font_size", "Font size: {font_size}% (+ / -)"),
+    ("ui.language", "Language: {language} (Left / Right)"),
+    ("ui.close_settings", "S: back"),
+];
+
+/// Get the source text of a user interface string.
+pub fn ui_source(id: &str) -> &'static str {
+    UI_STRINGS
+        .iter()
+        .find(|(ui_id, _)| *ui_id == id)
+        .map_or("", |(_, source)| source)
+}
+
+/// Single translated string.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Translation {
+    /// Translated text only.
+    Text(String),
+    /// Translated text, with the source text it was translated from, to detect stale
+    /// translations when the source text changes.
+    WithSource { text: String, source: String },
+}
+
+impl Translation {
+    /// Get the translated text.
+    pub fn text(&self) -> &str {
+        match self {
+            Translation::Text(text) => text,
+            Translation::WithSource { text, .. } => text,
+        }
+    }
+
+    /// Get the source text the string was translated from, if known.
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            Translation::Text(_) => None,
+            Translation::WithSource { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Table of translated strings for a single language, keyed by string identifier.
+#[derive(Deserialize, Clone, Default)]
+pub struct StringTable {
+    pub strings: HashMap<String, Translation>,
+}
+
+impl StringTable {
+    /// Parse a string table from its JSON representation.
+    pub fn parse(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Get the translation of a string, if any.
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.strings.get(id).map(Translation::text)
+    }
+}
+
+/// Bundle of Fluent messages of a single language.
+type MessageBundle = FluentBundle<Arc<FluentResource>, IntlLangMemoizer>;
+
+/// Languages of the current book, with their string tables and Fluent messages.
+#[derive(Default)]
+pub struct Localization {
+    /// Index of the current language, where 0 is the source language of the book.
+    pub language_index: usize,
+    /// Languages of the book, starting with the source language.
+    languages: Vec<Language>,
+    /// Handles of the string tables being loaded, by language code.
+    handles: HashMap<String, Handle<TextAsset>>,
+    /// Loaded string tables, by language code.
+    tables: HashMap<String, StringTable>,
+    /// Handles of the Fluent message files being loaded, by language code.
+    message_handles: HashMap<String, Handle<FluentAsset>>,
+    /// Loaded Fluent messages, by language code.
+    bundles: HashMap<String, MessageBundle>,
+}
+
+impl Localization {
+    /// Start loading the string tables and Fluent messages of all the languages of a book.
+    pub fn load(&mut self, book: &Book, asset_server: &AssetServer) {
+        self.languages = book.languages().cloned().collect();
+        self.language_index = 0;
+        self.tables.clear();
+        self.bundles.clear();
+        self.handles = book
+            .translations
+            .iter()
+            .filter_map(|language| {
+                let path = language.strings.as_ref()?;
+                Some((language.code.clone(), asset_server.load(path.as_str())))
+            })
+            .collect();
+        self.message_handles = book
+            .languages()
+            .filter_map(|language| {
+                let path = language.messages.as_ref()?;
+                Some((language.code.clone(), asset_server.load(path.as_str())))
+            })
+            .collect();
+    }
+
+    /// Parse the string tables and Fluent messages finished loading since the last call.
+    ///
+    /// Returns `true` if any of those is for the current language.
+    pub fn poll(
+        &mut self,
+        text_assets: &Assets<TextAsset>,
+        fluent_assets: &Assets<FluentAsset>,
+    ) -> bool {
+        let mut is_current_loaded = false;
+        let loaded: Vec<String> = self
+            .handles
+            .iter()
+            .filter(|(_, handle)| text_assets.get(*handle).is_some())
+            .map(|(code, _)| code.clone())
+            .collect();
+        for code in loaded {
+            let handle = self.handles.remove(&code).unwrap();
+            let json = &text_assets.get(handle).unwrap().value;
+            match StringTable::parse(json) {
+                Ok(table) => {
+                    is_current_loaded |= Some(&code) == self.code();
+                    self.tables.insert(code, table);
+                }
+                Err(err) => error!("Failed to parse string table for '{}': {}", code, err),
+            }
+        }
+        let loaded: Vec<String> = self
+            .message_handles
+            .iter()
+            .filter(|(_, handle)| fluent_assets.get(*handle).is_some())
+            .map(|(code, _)| code.clone())
+            .collect();
+        for code in loaded {
+            let handle = self.message_handles.remove(&code).unwrap();
+            let resource = fluent_assets.get(handle).unwrap().resource.clone();
+            let langid: LanguageIdentifier = code.parse().unwrap_or_else(|err| {
+                warn!("Invalid language code '{}': {}", code, err);
+                LanguageIdentifier::default()
+            });
+            let mut bundle = MessageBundle::new_concurrent(vec![langid]);
+            // Unicode isolation marks are not in most game fonts
+            bundle.set_use_isolating(false);
+            if let Err(errors) = bundle.add_resource(resource) {
+                for error in errors {
+                    warn!("Fluent messages for '{}': {}", code, error);
+                }
+            }
+            is_current_loaded |= Some(&code) == self.code();
+            self.bundles.insert(code, bundle);
+        }
+        is_current_loaded
+    }
+
+    /// Are any string tables or Fluent messages still loading?
+    pub fn is_loading(&self) -> bool {
+        !self.handles.is_empty() || !self.message_handles.is_empty()
+    }
+
+    /// Does the book have any translation to choose from?
+    pub fn has_translations(&self) -> bool {
+        self.languages.len() > 1
+    }
+
+    /// Get the code of the current language.
+    pub fn code(&self) -> Option<&String> {
+        self.languages
+            .get(self.language_index)
+            .map(|language| &language.code)
+    }
+
+    /// Get the display name of the current language.
+    pub fn name(&self) -> &str {
+        self.languages
+            .get(self.language_index)
+            .map_or("", |language| &language.name)
+    }
+
+    /// Get the writing direction of the current language.
+    pub fn direction(&self) -> TextDirection {
+        self.languages
+            .get(self.language_index)
+            .map_or(TextDirection::default(), |language| language.direction)
+    }
+
+    /// Cycle through the languages of the book, by `offset` positions.
+    pub fn cycle(&mut self, offset: isize) {
+        let count = self.languages.len() as isize;
+        if count > 0 {
+            self.language_index =
+                (self.language_index as isize + offset).rem_euclid(count) as usize;
+        }
+    }
+
+    /// Translate a string into the current language, falling back to its source text.
+    pub fn tr<'a>(&'a self, id: &str, source: &'a str) -> Cow<'a, str> {
+        self.tr_args(id, source, &[])
+    }
+
+    /// Translate a string with arguments into the current language.
+    ///
+    /// The Fluent message of the current language is used first, with the arguments as
+    /// Fluent variables. Otherwise the string table translation, or the source text, is
+    /// used with the `{name}` placeholders replaced by the argument values.
+    pub fn tr_args<'a>(
+        &'a self,
+        id: &str,
+        source: &'a str,
+        args: &[(&str, FluentValue)],
+    ) -> Cow<'a, str> {
+        let code = self.code();
+        if let Some(bundle) = code.and_then(|code| self.bundles.get(code)) {
+            let pattern = bundle
+                .get_message(&message_id(id))
+                .and_then(|message| message.value());
+            if let Some(pattern) = pattern {
+                let mut fluent_args = FluentArgs::new();
+                for (name, value) in args {
+                    fluent_args.set(*name, value.clone());
+                }
+                let mut errors = vec![];
+                let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+                for error in errors {
+                    warn!("Message '{}': {}", id, error);
+                }
+                return Cow::Owned(text.into_owned());
+            }
+        }
+        let text = code
+            .and_then(|code| self.tables.get(code))
+            .and_then(|table| table.get(id))
+            .unwrap_or(source);
+        if args.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        let mut text = text.to_string();
+        for (name, value) in args {
+            let value = match value {
+                FluentValue::String(value) => value.clone(),
+                FluentValue::Number(value) => value.as_string(),
+                _ => Cow::Borrowed(""),
+            };
+            text = text.replace(&format!("{{{}}}", name), &value);
+        }
+        Cow::Owned(text)
+    }
+
+    /// Translate a user interface string into the current language.
+    pub fn tr_ui(&self, id: &str) -> Cow<'_, str> {
+        self.tr(id, ui_source(id))
+    }
+
+    /// Translate a user interface string with arguments into the current language.
+    pub fn tr_ui_args(&self, id: &str, args: &[(&str, FluentValue)]) -> Cow<'_, str> {
+        self.tr_args(id, ui_source(id), args)
+    }
+}
+
+/// Get the Fluent message identifier of a string identifier.
+///
+/// Fluent identifiers can't contain dots, so those are replaced with dashes, and any
+/// other invalid character with an underscore; `ui.pages_read` becomes `ui-pages_read`.
+pub fn message_id(id: &str) -> String {
+    id.chars()
+        .map(|c| match c {
+            '.' => '-',
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Collect the identifier and source text of all the translatable strings of a book,
+/// including the user interface strings.
+pub fn collect_strings(book: &Book) -> Vec<(String, String)> {
+    let mut strings = vec![];
+    for (page_index, page) in book.pages.iter().enumerate() {
+        for (line_index, line) in page.lines.iter().enumerate() {
+            strings.push((book.line_id(page_index, line_index), line.text.clone()));
+        }
+        for (key, button) in page.buttons.iter().flatten() {
+            strings.push((book.button_id(Some(page_index), key), button.text.clone()));
+        }
+    }
+    for (key, button) in &book.default_buttons {
+        strings.push((book.button_id(None, key), button.text.clone()));
+    }
+    for (id, source) in UI_STRINGS {
+        strings.push((id.to_string(), source.to_string()));
+    }
+    strings
+}
+
+/// Issues found in the string table of a language.
+#[derive(Default)]
+pub struct LintReport {
+    /// Identifiers of strings without translation.
+    pub untranslated: Vec<String>,
+    /// Identifiers of strings translated from a source text which since changed.
+    pub stale: Vec<String>,
+    /// Identifiers of translations not matching any string of the book.
+    pub unused: Vec<String>,
+}
+
+impl LintReport {
+    pub fn is_empty(&self) -> bool {
+        self.untranslated.is_empty() && self.stale.is_empty() && self.unused.is_empty()
+    }
+}
+
+/// Check a string table and Fluent messages against the strings of a book.
+///
+/// A string is translated if either the string table or the Fluent messages have it.
+/// Only string table entries can be detected as stale, as Fluent messages don't record
+/// their source text.
+pub fn lint(book: &Book, table: &StringTable, message_ids: &[String]) -> LintReport {
+    let strings = collect_strings(book);
+    let mut report = LintReport::default();
+    for (id, source) in &strings {
+        match table.strings.get(id) {
+            None => {
+                if !message_ids.contains(&message_id(id)) {
+                    report.untranslated.push(id.clone());
+                }
+            }
+            Some(translation) => {
+                if translation.source().is_some_and(|s| s != source) {
+                    report.stale.push(id.clone());
+                }
+            }
+        }
+    }
+    for id in table.strings.keys() {
+        if !strings.iter().any(|(string_id, _)| string_id == id) {
+            report.unused.push(id.clone());
+        }
+    }
+    for id in message_ids {
+        if !strings
+            .iter()
+            .any(|(string_id, _)| message_id(string_id) == *id)
+        {
+            report.unused.push(id.clone());
+        }
+    }
+    report.unused.sort();
+    report
+}
+
+/// Get the identifiers of the messages of a Fluent file.
+fn read_message_ids(path: &Path) -> Result<Vec<String>, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let resource = FluentResource::try_new(source).map_err(|(_, errors)| {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    })?;
+    Ok(resource
+        .entries()
+        .filter_map(|entry| match entry {
+            ast::Entry::Message(message) => Some(message.id.name.to_string()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Lint the string tables and Fluent messages of all the translations of a book,
+/// printing a report.
+///
+/// Asset paths are relative to `asset_dir`. Returns `true` if no issue was found.
+pub fn lint_translations(book: &Book, asset_dir: &Path) -> bool {
+    let mut is_clean = true;
+    for Language {
+        code,
+        strings,
+        messages,
+        ..
+    } in &book.translations
+    {
+        if strings.is_none() && messages.is_none() {
+            println!("[{}] no string table or messages", code);
+            is_clean = false;
+            continue;
+        }
+        let table = match strings {
+            Some(path) => {
+                let path = asset_dir.join(path);
+                match std::fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|json| StringTable::parse(&json).map_err(|err| err.to_string()))
+                {
+                    Ok(table) => table,
+                    Err(err) => {
+                        println!("[{}] failed to load {}: {}", code, path.display(), err);
+                        is_clean = false;
+                        continue;
+                    }
+                }
+            }
+            None => StringTable::default(),
+        };
+        let message_ids = match messages {
+            Some(path) => {
+                let path = asset_dir.join(path);
+                match read_message_ids(&path) {
+                    Ok(message_ids) => message_ids,
+                    Err(err) => {
+                        println!("[{}] failed to load {}: {}", code, path.display(), err);
+                        is_clean = false;
+                        continue;
+                    }
+                }
+            }
+            None => vec![],
+        };
+        let report = lint(book, &table, &message_ids);
+        for id in &report.untranslated {
+            println!("[{}] untranslated: {}", code, id);
+        }
+        for id in &report.stale {
+            println!("[{}] stale: {}", code, id);
+        }
+        for id in &report.unused {
+            println!("[{}] unused: {}", code, id);
+        }
+        if report.is_empty() {
+            println!("[{}] ok", code);
+        } else {
+            is_clean = false;
+        }
+    }
+    is_clean
+}
@@ -0,0 +1,397 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::book::*;
+
+/// Section of a Markdown book, before or under a heading.
+struct Section {
+    /// Page name, or `None` for the section before the first heading.
+    name: Option<String>,
+    /// Settings of the front-matter block, with the line number of each entry.
+    settings: Vec<(usize, String, String)>,
+    /// Body rows, with their line number.
+    rows: Vec<(usize, String)>,
+}
+
+/// Convert a heading text into the name of its page, like the anchors of rendered
+/// Markdown: `"The Big Door"` becomes `"the-big-door"`.
+///
+/// An explicit name can be given with a trailing `{#name}` attribute instead.
+fn heading_name(heading: &str) -> String {
+    if let Some(start) = heading.rfind("{#") {
+        if let Some(name) = heading[start + 2..].strip_suffix('}') {
+            return name.trim().to_string();
+        }
+    }
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' | '-' => Some('-'),
+            c if c.is_alphanumeric() || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Get the text of a heading row, if the row is a heading.
+fn heading(row: &str) -> Option<&str> {
+    let text = row.trim_start_matches('#');
+    let level = row.len() - text.len();
+    let is_heading = (1..=6).contains(&level) && (text.is_empty() || text.starts_with(' '));
+    is_heading.then(|| text.trim())
+}
+
+/// Split a Markdown source into its preamble, before the first heading, and the
+/// sections of its headings.
+///
+/// The preamble is empty if the source starts with a heading.
+fn parse_sections(source: &str) -> (Section, Vec<Section>) {
+    let mut sections = vec![Section {
+        name: None,
+        settings: vec![],
+        rows: vec![],
+    }];
+    let mut in_front_matter = false;
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let section = sections.last_mut().unwrap();
+        if line.trim_end() == "---" {
+            // Front-matter only starts at the top of a section
+            if in_front_matter || section.rows.iter().all(|(_, row)| row.trim().is_empty()) {
+                in_front_matter = !in_front_matter;
+                continue;
+            }
+        }
+        if in_front_matter {
+            if let Some((key, value)) = line.split_once(':') {
+                let entry = (
+                    line_number,
+                    key.trim().to_string(),
+                    value.trim().to_string(),
+                );
+                section.settings.push(entry);
+            }
+        } else if let Some(heading) = heading(line) {
+            sections.push(Section {
+                name: Some(heading_name(heading)),
+                settings: vec![],
+                rows: vec![],
+            });
+        } else {
+            section.rows.push((line_number, line.to_string()));
+        }
+    }
+    let preamble = sections.remove(0);
+    (preamble, sections)
+}
+
+/// Apply front-matter settings to the fields of a value, by field name.
+///
+/// Setting values are parsed as JSON if possible, like `30` or `true`, and otherwise
+/// taken as a string, like `Center`.
+fn apply_settings<T: Serialize + DeserializeOwned>(
+    value: T,
+    settings: &[(usize, String, String)],
+) -> Result<T, String> {
+    let mut object = match serde_json::to_value(value).map_err(|err| err.to_string())? {
+        Value::Object(object) => object,
+        _ => Map::new(),
+    };
+    for (line_number, key, setting) in settings {
+        if !object.contains_key(key) {
+            return Err(format!("Line {}: unknown setting '{}'", line_number, key));
+        }
+        let setting =
+            serde_json::from_str(setting).unwrap_or_else(|_| Value::String(setting.clone()));
+        object.insert(key.clone(), setting);
+    }
+    serde_json::from_value(Value::Object(object)).map_err(|err| {
+        let lines: Vec<String> = settings.iter().map(|s| s.0.to_string()).collect();
+        format!("Lines {}: invalid settings: {}", lines.join(", "), err)
+    })
+}
+
+/// Convert a single body row, collecting its links.
+///
+/// Links to a page, like `[Label](#page-name)`, are replaced by their label, and
+/// emphasis markers are removed. Rows made only of links are dropped entirely, as the
+/// links already become buttons.
+fn convert_row(row: &str, links: &mut Vec<(String, String)>) -> String {
+    let mut text = String::new();
+    let mut has_prose = false;
+    let mut rest = row;
+    while let Some(c) = rest.chars().next() {
+        if c == '[' {
+            // The label ends at the first `]`, which must be followed by the target
+            let link = rest[1..].split_once(']').and_then(|(label, after)| {
+                let (target, after) = after.strip_prefix("(#")?.split_once(')')?;
+                Some((label, target, after))
+            });
+            if let Some((label, target, after)) = link {
+                text.push_str(label);
+                links.push((label.to_string(), target.to_string()));
+                rest = after;
+                continue;
+            }
+        }
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\\' => {
+                if let Some(c) = rest.chars().next() {
+                    text.push(c);
+                    has_prose = true;
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+            '*' => {}
+            c => {
+                has_prose |= !c.is_whitespace();
+                text.push(c);
+            }
+        }
+    }
+    if has_prose {
+        text.trim().to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Parse a Markdown book.
+///
+/// Each heading starts a page named after it (see [`heading_name`]), without showing the
+/// heading itself, and each non-empty row of its body becomes a line. Links to pages
+/// become buttons jumping to the target page: a single link uses the space key, and
+/// multiple links the number keys. Pages without links show the default continue
+/// button, or end the book for the last page.
+///
+/// A front-matter block delimited by `---` rows at the top of the file sets the fields
+/// of the book, and at the top of a page the fields of the page, as `field: value`
+/// entries:
+///
+/// ```md
+/// ---
+/// text_align: Center
+/// ---
+///
+/// # Title
+/// ---
+/// style: title
+/// ---
+/// A short word...
+/// ```
+pub fn parse(source: &str) -> Result<Book, String> {
+    let (preamble, sections) = parse_sections(source);
+    let mut book = Book::default();
+    book.default_buttons.insert(
+        "space".to_string(),
+        Button {
+            id: None,
+            text: "Continue".to_string(),
+            action: ButtonAction::NextPage,
+            style: None,
+        },
+    );
+    let mut book = apply_settings(book, &preamble.settings)?;
+    let text_row = preamble.rows.iter().find(|(_, row)| !row.trim().is_empty());
+    if let Some((line_number, _)) = text_row {
+        return Err(format!(
+            "Line {}: text before the first heading, outside of any page",
+            line_number
+        ));
+    }
+
+    for section in sections {
+        let name = section.name.unwrap_or_default();
+        let mut links = vec![];
+        let lines = section
+            .rows
+            .iter()
+            .map(|(_, row)| convert_row(row, &mut links))
+            .filter(|row| !row.is_empty())
+            .map(|text| Line {
+                text,
+                ..Default::default()
+            })
+            .collect();
+
+        if links.len() > CHOICE_KEYS.len() {
+            return Err(format!(
+                "Page '{}': {} links, only up to {} are supported",
+                name,
+                links.len(),
+                CHOICE_KEYS.len()
+            ));
+        }
+        let is_single_link = links.len() == 1;
+        let buttons: HashMap<String, Button> = links
+            .into_iter()
+            .enumerate()
+            .map(|(index, (text, target))| {
+                let key = if is_single_link {
+                    "space"
+                } else {
+                    CHOICE_KEYS[index]
+                };
+                let button = Button {
+                    id: None,
+                    text,
+                    action: ButtonAction::JumpToPage(target),
+                    style: None,
+                };
+                (key.to_string(), button)
+            })
+            .collect();
+
+        let page = Page {
+            name: Some(name),
+            lines,
+            buttons: if buttons.is_empty() {
+                None
+            } else {
+                Some(buttons)
+            },
+            ..Default::default()
+        };
+        book.pages.push(apply_settings(page, &section.settings)?);
+    }
+
+    if let Some(page) = book.pages.last_mut() {
+        if page.buttons.is_none() {
+            page.is_final = true;
+        }
+    }
+    Ok(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the buttons of a page, as sorted key, text and target page triples.
+    fn buttons(page: &Page) -> Vec<(&str, &str, &str)> {
+        let mut buttons: Vec<_> = page
+            .buttons
+            .iter()
+            .flatten()
+            .map(|(key, button)| match &button.action {
+                ButtonAction::JumpToPage(target) => {
+                    (key.as_str(), button.text.as_str(), target.as_str())
+                }
+                _ => panic!("Unexpected action of button {}", key),
+            })
+            .collect();
+        buttons.sort();
+        buttons
+    }
+
+    fn lines(page: &Page) -> Vec<&str> {
+        page.lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn headings_and_links() {
+        let source = "\
+# The Big Door
+A *huge* door, see [the hall](#hall).
+[Open it](#inside) [Knock](#the-big-door)
+
+## Hall {#hall}
+Press [Y] or [go back](#the-big-door).
+
+# Inside
+A link to [a website](https://example.com).
+";
+        let book = parse(source).unwrap();
+        let names: Vec<_> = book.pages.iter().map(|page| page.name.as_deref()).collect();
+        assert_eq!(
+            names,
+            vec![Some("the-big-door"), Some("hall"), Some("inside")]
+        );
+
+        let page = &book.pages[0];
+        assert_eq!(lines(page), vec!["A huge door, see the hall."]);
+        assert_eq!(
+            buttons(page),
+            vec![
+                ("1", "the hall", "hall"),
+                ("2", "Open it", "inside"),
+                ("3", "Knock", "the-big-door"),
+            ]
+        );
+        assert!(!page.is_final);
+
+        // Brackets without target stay as text
+        assert_eq!(lines(&book.pages[1]), vec!["Press [Y] or go back."]);
+        assert_eq!(
+            buttons(&book.pages[1]),
+            vec![("space", "go back", "the-big-door")]
+        );
+
+        // External links stay as text, and the last page without links ends the book
+        let page = &book.pages[2];
+        assert_eq!(
+            lines(page),
+            vec!["A link to [a website](https://example.com)."]
+        );
+        assert!(page.buttons.is_none());
+        assert!(page.is_final);
+    }
+
+    #[test]
+    fn front_matter_and_preamble() {
+        // Without preamble
+        let book = parse("# Only\nText").unwrap();
+        assert_eq!(book.pages.len(), 1);
+        assert_eq!(book.pages[0].name.as_deref(), Some("only"));
+        assert!(parse("").unwrap().pages.is_empty());
+
+        let source = "\
+---
+text_width: 600
+---
+
+# Title
+---
+style: title
+---
+Text
+";
+        let book = parse(source).unwrap();
+        assert_eq!(book.text_width, Some(600.));
+        assert_eq!(book.pages[0].style.as_deref(), Some("title"));
+        assert_eq!(lines(&book.pages[0]), vec!["Text"]);
+
+        let err = parse("Lost text\n# Title\n").err().unwrap();
+        assert_eq!(
+            err,
+            "Line 1: text before the first heading, outside of any page"
+        );
+        let err = parse("---\ncolour: red\n---\n").err().unwrap();
+        assert_eq!(err, "Line 2: unknown setting 'colour'");
+    }
+
+    #[test]
+    fn links_to_missing_pages() {
+        let book = parse("# Start\n[Nowhere](#missing)\n").unwrap();
+        let action = &book.pages[0].buttons.as_ref().unwrap()["space"].action;
+        assert_eq!(book.destination(0, action), Destination::Missing);
+
+        // Up to 9 links become buttons
+        let links =
+            |count: usize| -> String { (1..=count).map(|i| format!("[{}](#start)", i)).collect() };
+        let book = parse(&format!("# Start\n{}\n", links(9))).unwrap();
+        let keys: Vec<_> = buttons(&book.pages[0])
+            .into_iter()
+            .map(|(key, text, _)| (key, text))
+            .collect();
+        let expected: Vec<_> = CHOICE_KEYS.iter().map(|key| (*key, *key)).collect();
+        assert_eq!(keys, expected);
+
+        let err = parse(&format!("# Start\n{}\n", links(10))).err().unwrap();
+        assert_eq!(err, "Page 'start': 10 links, only up to 9 are supported");
+    }
+}
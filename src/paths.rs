@@ -0,0 +1,552 @@
+use std::fmt;
+
+use crate::book::*;
+
+/// Maximum number of pages visited while enumerating the paths, to keep the analysis of
+/// large books bounded, with or without endings.
+const MAX_STEPS: usize = 1_000_000;
+
+/// Paths leading to one ending of a book.
+pub struct Ending {
+    /// Index of the page the run ends from.
+    pub page_index: usize,
+    /// Number of distinct paths to this ending, without revisiting any page.
+    pub path_count: usize,
+    /// Pages read by the shortest path, as counted by the scoreboard.
+    pub min_pages_read: u32,
+    /// Pages read by the longest path without revisiting any page.
+    pub max_pages_read: u32,
+    /// Pages of the shortest path, starting with the first page.
+    pub shortest_path: Vec<usize>,
+    /// Pages of the longest path, starting with the first page.
+    pub longest_path: Vec<usize>,
+}
+
+/// Result of the analysis of all the playthroughs of a book.
+pub struct PathReport {
+    /// Endings, in page order.
+    pub endings: Vec<Ending>,
+    /// Number of distinct paths to any ending.
+    pub path_count: usize,
+    /// Did the enumeration stop at [`MAX_STEPS`], making counts and scores partial?
+    pub is_truncated: bool,
+    /// Loops reachable from the first page, as the pages of each loop. A loop from which
+    /// an ending is reachable can inflate the pages read forever.
+    pub loops: Vec<Vec<usize>>,
+    /// Is any ending reachable from one of the loops?
+    pub is_score_unbounded: bool,
+    /// Non-final pages without any button leading to another page or the end.
+    pub dead_ends: Vec<usize>,
+    /// Pages not reachable from the first page.
+    pub unreachable: Vec<usize>,
+    /// Page keys, for display.
+    page_keys: Vec<String>,
+}
+
+impl PathReport {
+    /// Highest score reachable without revisiting any page, or `None` if the book has no
+    /// ending. With [`PathReport::is_score_unbounded`], loops can exceed it.
+    pub fn best_score(&self) -> Option<u32> {
+        self.endings
+            .iter()
+            .map(|ending| ending.max_pages_read)
+            .max()
+    }
+
+    fn path_names(&self, path: &[usize]) -> String {
+        let names: Vec<&str> = path.iter().map(|&i| self.page_keys[i].as_str()).collect();
+        names.join(" -> ")
+    }
+
+    fn page_names(&self, pages: &[usize]) -> String {
+        let names: Vec<&str> = pages.iter().map(|&i| self.page_keys[i].as_str()).collect();
+        names.join(", ")
+    }
+}
+
+impl fmt::Display for PathReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} path(s) to {} ending(s){}",
+            self.path_count,
+            self.endings.len(),
+            if self.is_truncated {
+                " (truncated, counts and scores are partial)"
+            } else {
+                ""
+            }
+        )?;
+        for ending in &self.endings {
+            writeln!(
+                f,
+                "- ending '{}': {} path(s), {} to {} pages read",
+                self.page_keys[ending.page_index],
+                ending.path_count,
+                ending.min_pages_read,
+                ending.max_pages_read
+            )?;
+            writeln!(f, "  shortest: {}", self.path_names(&ending.shortest_path))?;
+            writeln!(f, "  longest: {}", self.path_names(&ending.longest_path))?;
+        }
+        match self.best_score() {
+            Some(score) if self.is_score_unbounded => writeln!(
+                f,
+                "best score: unbounded, loops can inflate it past {} pages read",
+                score
+            )?,
+            Some(score) => writeln!(f, "best score: {} pages read", score)?,
+            None => writeln!(f, "best score: none, the book has no ending")?,
+        }
+        for pages in &self.loops {
+            writeln!(f, "loop: {}", self.page_names(pages))?;
+        }
+        if !self.dead_ends.is_empty() {
+            writeln!(f, "dead ends: {}", self.page_names(&self.dead_ends))?;
+        }
+        if !self.unreachable.is_empty() {
+            writeln!(f, "unreachable: {}", self.page_names(&self.unreachable))?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the destinations of the buttons of each page, in button key order.
+fn destinations(book: &Book) -> Vec<Vec<Destination>> {
+    (0..book.pages.len())
+        .map(|page_index| {
+            let page = &book.pages[page_index];
+            let buttons = page.buttons.as_ref().unwrap_or(&book.default_buttons);
+            let mut buttons: Vec<_> = buttons.iter().collect();
+            buttons.sort_by(|a, b| a.0.cmp(b.0));
+            buttons
+                .into_iter()
+                .map(|(_, button)| book.destination(page_index, &button.action))
+                .collect()
+        })
+        .collect()
+}
+
+/// Get the pages reachable by the buttons of a page.
+fn next_pages(destinations: &[Destination]) -> impl Iterator<Item = usize> + '_ {
+    destinations
+        .iter()
+        .filter_map(|destination| match destination {
+            Destination::Page(index) => Some(*index),
+            _ => None,
+        })
+}
+
+/// Depth-first enumeration of the paths without revisiting any page.
+struct Walker<'a> {
+    destinations: &'a [Vec<Destination>],
+    path: Vec<usize>,
+    on_path: Vec<bool>,
+    endings: Vec<Option<Ending>>,
+    path_count: usize,
+    step_count: usize,
+    is_truncated: bool,
+}
+
+impl Walker<'_> {
+    /// Enumerate the paths from the first page, with an explicit stack so that long
+    /// books don't overflow the call stack.
+    fn walk(&mut self) {
+        // Index of the next destination to follow, for each page of the path
+        let mut cursors = vec![];
+        self.enter(0);
+        cursors.push(0);
+        while let Some(cursor) = cursors.last_mut() {
+            let page_index = *self.path.last().unwrap();
+            let destinations = &self.destinations[page_index];
+            let index = *cursor;
+            *cursor += 1;
+            match destinations.get(index) {
+                None => {
+                    cursors.pop();
+                    self.path.pop();
+                    self.on_path[page_index] = false;
+                }
+                Some(Destination::Page(next)) if !self.on_path[*next] => {
+                    if self.step_count >= MAX_STEPS {
+                        self.is_truncated = true;
+                        return;
+                    }
+                    self.enter(*next);
+                    cursors.push(0);
+                }
+                // Several buttons ending the run from the same page are the same ending
+                Some(Destination::End) if !destinations[..index].contains(&Destination::End) => {
+                    self.record_ending(page_index);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn enter(&mut self, page_index: usize) {
+        self.step_count += 1;
+        self.path.push(page_index);
+        self.on_path[page_index] = true;
+    }
+
+    fn record_ending(&mut self, page_index: usize) {
+        self.path_count += 1;
+        // Each page of the path is read once the player presses one of its buttons
+        let pages_read = self.path.len() as u32;
+        let ending = self.endings[page_index].get_or_insert_with(|| Ending {
+            page_index,
+            path_count: 0,
+            min_pages_read: pages_read,
+            max_pages_read: pages_read,
+            shortest_path: self.path.clone(),
+            longest_path: self.path.clone(),
+        });
+        ending.path_count += 1;
+        if pages_read < ending.min_pages_read {
+            ending.min_pages_read = pages_read;
+            ending.shortest_path = self.path.clone();
+        }
+        if pages_read > ending.max_pages_read {
+            ending.max_pages_read = pages_read;
+            ending.longest_path = self.path.clone();
+        }
+    }
+}
+
+/// Find the loops of the pages reachable from the first page, as the strongly connected
+/// components of the page graph with more than one page or a page leading to itself.
+fn find_loops(destinations: &[Vec<Destination>], reachable: &[bool]) -> Vec<Vec<usize>> {
+    // Tarjan's algorithm
+    struct State<'a> {
+        destinations: &'a [Vec<Destination>],
+        index: Vec<Option<usize>>,
+        low_link: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next_index: usize,
+        loops: Vec<Vec<usize>>,
+    }
+
+    fn visit(state: &mut State, page: usize) {
+        state.index[page] = Some(state.next_index);
+        state.low_link[page] = state.next_index;
+        state.next_index += 1;
+        state.stack.push(page);
+        state.on_stack[page] = true;
+    }
+
+    /// Find the components reachable from `root`, with an explicit stack of the pages
+    /// being connected, and the index of the next destination to follow for each.
+    fn connect(state: &mut State, root: usize) {
+        visit(state, root);
+        let mut calls = vec![(root, 0)];
+        while let Some((page, cursor)) = calls.last_mut() {
+            let page = *page;
+            if let Some(destination) = state.destinations[page].get(*cursor) {
+                *cursor += 1;
+                if let Destination::Page(next) = *destination {
+                    match state.index[next] {
+                        None => {
+                            visit(state, next);
+                            calls.push((next, 0));
+                        }
+                        Some(index) if state.on_stack[next] => {
+                            state.low_link[page] = state.low_link[page].min(index);
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                state.low_link[parent] = state.low_link[parent].min(state.low_link[page]);
+            }
+            if Some(state.low_link[page]) == state.index[page] {
+                let mut component = vec![];
+                while let Some(other) = state.stack.pop() {
+                    state.on_stack[other] = false;
+                    component.push(other);
+                    if other == page {
+                        break;
+                    }
+                }
+                let is_self_loop = next_pages(&state.destinations[page]).any(|next| next == page);
+                if component.len() > 1 || is_self_loop {
+                    component.sort_unstable();
+                    state.loops.push(component);
+                }
+            }
+        }
+    }
+
+    let page_count = destinations.len();
+    let mut state = State {
+        destinations,
+        index: vec![None; page_count],
+        low_link: vec![0; page_count],
+        on_stack: vec![false; page_count],
+        stack: vec![],
+        next_index: 0,
+        loops: vec![],
+    };
+    for (page, &is_reachable) in reachable.iter().enumerate() {
+        if is_reachable && state.index[page].is_none() {
+            connect(&mut state, page);
+        }
+    }
+    state.loops.sort();
+    state.loops
+}
+
+/// Get the pages reachable from the first page.
+fn reachable_pages(destinations: &[Vec<Destination>]) -> Vec<bool> {
+    let mut reachable = vec![false; destinations.len()];
+    let mut stack = vec![];
+    if !destinations.is_empty() {
+        stack.push(0);
+    }
+    while let Some(page) = stack.pop() {
+        if !reachable[page] {
+            reachable[page] = true;
+            stack.extend(next_pages(&destinations[page]));
+        }
+    }
+    reachable
+}
+
+/// Get the pages from which an ending is reachable.
+fn ending_pages(destinations: &[Vec<Destination>]) -> Vec<bool> {
+    let mut can_end: Vec<bool> = destinations
+        .iter()
+        .map(|page| page.contains(&Destination::End))
+        .collect();
+    let mut is_changed = true;
+    while is_changed {
+        is_changed = false;
+        for page in 0..destinations.len() {
+            if !can_end[page] && next_pages(&destinations[page]).any(|next| can_end[next]) {
+                can_end[page] = true;
+                is_changed = true;
+            }
+        }
+    }
+    can_end
+}
+
+/// Analyze all the playthroughs of a book, from its first page to each of its endings.
+///
+/// A playthrough ends when the player presses a button of a final page, or a button
+/// jumping to the end. Paths revisiting a page are not enumerated, but the loops
+/// allowing them are reported, as they let the player read as many pages as they want.
+pub fn analyze(book: &Book) -> PathReport {
+    let destinations = destinations(book);
+    let page_count = book.pages.len();
+    let mut walker = Walker {
+        destinations: &destinations,
+        path: vec![],
+        on_path: vec![false; page_count],
+        endings: (0..page_count).map(|_| None).collect(),
+        path_count: 0,
+        step_count: 0,
+        is_truncated: false,
+    };
+    if page_count > 0 {
+        walker.walk();
+    }
+
+    let reachable = reachable_pages(&destinations);
+    let can_end = ending_pages(&destinations);
+    let loops = find_loops(&destinations, &reachable);
+    let is_score_unbounded = loops
+        .iter()
+        .any(|pages| pages.iter().any(|&page| can_end[page]));
+    let dead_ends = (0..page_count)
+        .filter(|&page| {
+            !book.pages[page].is_final
+                && destinations[page]
+                    .iter()
+                    .all(|destination| *destination == Destination::Missing)
+        })
+        .collect();
+    let unreachable = (0..page_count).filter(|&page| !reachable[page]).collect();
+
+    PathReport {
+        endings: walker.endings.into_iter().flatten().collect(),
+        path_count: walker.path_count,
+        is_truncated: walker.is_truncated,
+        loops,
+        is_score_unbounded,
+        dead_ends,
+        unreachable,
+        page_keys: (0..page_count).map(|i| book.page_key(i)).collect(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn page(name: &str, text: &str, buttons: &[(&str, &str, ButtonAction)]) -> Page {
+        let buttons: HashMap<String, Button> = buttons
+            .iter()
+            .map(|(key, text, action)| {
+                let button = Button {
+                    id: None,
+                    text: text.to_string(),
+                    action: action.clone(),
+                    style: None,
+                };
+                (key.to_string(), button)
+            })
+            .collect();
+        Page {
+            name: Some(name.to_string()),
+            lines: vec![Line {
+                text: text.to_string(),
+                ..Default::default()
+            }],
+            buttons: (!buttons.is_empty()).then_some(buttons),
+            ..Default::default()
+        }
+    }
+
+    fn jump(name: &str) -> ButtonAction {
+        ButtonAction::JumpToPage(name.to_string())
+    }
+
+    /// Book with two endings, a loop between its first two pages, a dead end and an
+    /// unreachable page.
+    pub(crate) fn sample_book() -> Book {
+        let mut exit = page(
+            "exit",
+            "Daylight!",
+            &[("space", "The end", ButtonAction::NextPage)],
+        );
+        exit.is_final = true;
+        let pages = vec![
+            page(
+                "start",
+                "You wake up in a dark room.",
+                &[
+                    ("1", "Open the door", jump("hall")),
+                    ("2", "Sleep", ButtonAction::JumpToEnd),
+                    ("3", "Climb out the window", jump("exit")),
+                ],
+            ),
+            page(
+                "hall",
+                "A long hall.",
+                &[
+                    ("1", "Go back", jump("start")),
+                    ("2", "Go on", jump("exit")),
+                    ("3", "Take the stairs", jump("cellar")),
+                ],
+            ),
+            exit,
+            page("cellar", "Nothing but dust.", &[]),
+            page(
+                "secret",
+                "A secret room.",
+                &[("space", "Back", jump("start"))],
+            ),
+        ];
+        Book {
+            pages,
+            default_buttons: HashMap::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn analyzes_paths() {
+        let report = analyze(&sample_book());
+        assert_eq!(report.path_count, 3);
+        assert!(!report.is_truncated);
+
+        let endings: Vec<_> = report.endings.iter().map(|e| e.page_index).collect();
+        assert_eq!(endings, vec![0, 2]);
+        let sleep = &report.endings[0];
+        assert_eq!(sleep.path_count, 1);
+        assert_eq!((sleep.min_pages_read, sleep.max_pages_read), (1, 1));
+        assert_eq!(sleep.shortest_path, vec![0]);
+        let exit = &report.endings[1];
+        assert_eq!(exit.path_count, 2);
+        assert_eq!((exit.min_pages_read, exit.max_pages_read), (2, 3));
+        assert_eq!(exit.shortest_path, vec![0, 2]);
+        assert_eq!(exit.longest_path, vec![0, 1, 2]);
+        assert_eq!(report.best_score(), Some(3));
+
+        assert_eq!(report.loops, vec![vec![0, 1]]);
+        assert!(report.is_score_unbounded);
+        assert_eq!(report.dead_ends, vec![3]);
+        assert_eq!(report.unreachable, vec![4]);
+
+        let text = report.to_string();
+        assert!(
+            text.contains("  longest: start -> hall -> exit\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("loop: start, hall\ndead ends: cellar\nunreachable: secret\n"));
+    }
+
+    /// Book of `page_count` pages, each leading to the next one and the one after, and
+    /// wrapping around to the first page instead of ending.
+    fn endless_book(page_count: usize) -> Book {
+        let pages = (0..page_count)
+            .map(|index| {
+                page(
+                    &index.to_string(),
+                    "Again",
+                    &[
+                        ("1", "Next", ButtonAction::NextPage),
+                        ("2", "Skip", jump(&(index + 2).to_string())),
+                    ],
+                )
+            })
+            .collect();
+        Book {
+            pages,
+            default_buttons: HashMap::new(),
+            end_of_book: Some(EndOfBook::Wrap),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bounds_books_without_ending() {
+        let report = analyze(&endless_book(70));
+        assert!(report.is_truncated);
+        assert_eq!(report.path_count, 0);
+        assert!(report.endings.is_empty());
+        assert_eq!(report.loops.len(), 1);
+        assert!(!report.is_score_unbounded);
+        assert_eq!(report.dead_ends, Vec::<usize>::new());
+        assert!(report.unreachable.is_empty());
+    }
+
+    #[test]
+    fn analyzes_long_books() {
+        let mut book = endless_book(0);
+        book.pages = (0..100_000)
+            .map(|_| page("", "Again", &[("space", "Next", ButtonAction::NextPage)]))
+            .collect();
+        let report = analyze(&book);
+        assert!(!report.is_truncated);
+        assert!(report.endings.is_empty());
+        assert_eq!(report.loops.len(), 1);
+        assert_eq!(report.loops[0].len(), 100_000);
+    }
+
+    #[test]
+    fn analyzes_empty_book() {
+        let report = analyze(&Book::default());
+        assert_eq!(report.path_count, 0);
+        assert_eq!(report.best_score(), None);
+        assert!(report.to_string().contains("best score: none"));
+    }
+}
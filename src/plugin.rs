@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::fluent_asset::FluentAssetPlugin;
+use crate::scroll;
+use crate::text_asset::TextAssetPlugin;
+use crate::{events, state, text_system};
+
+/// Labels of the systems of the [`StoryPlugin`], to order other systems against.
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StorySystem {
+    /// Handles the inputs and updates the story and its UI.
+    Update,
+    /// Sends the events of the story, like [`crate::PageEntered`], raised by
+    /// [`StorySystem::Update`] during the same frame. Runs in [`CoreStage::PostUpdate`].
+    SendEvents,
+}
+
+/// Paths of the assets of a story, relative to the asset directory.
+#[derive(Clone)]
+pub struct StoryAssets {
+    /// Book of the story, in any of the formats of [`crate::book::BookFormat`].
+    pub book: String,
+    /// Built-in font, used for the characters the fonts of the book don't have.
+    pub font: String,
+    /// Images of the keyboard keys activating the buttons, by button key.
+    pub button_images: HashMap<String, String>,
+}
+
+impl Default for StoryAssets {
+    fn default() -> Self {
+        let button_images = text_system::BUTTON_KEYS
+            .iter()
+            .map(|(key, _)| (key.to_string(), format!("key_{}.png", key)))
+            .collect();
+        StoryAssets {
+            book: "text.json".to_string(),
+            font: "fonts/mochiy_pop_one/MochiyPopOne-Regular.ttf".to_string(),
+            button_images,
+        }
+    }
+}
+
+/// Plugin playing a branching story: loads a book, shows its pages one at a time, moves
+/// through them with the keyboard, and shows a scoreboard at the end of each run.
+///
+/// The state of the story is in the [`crate::TextSystem`] resource, created on startup,
+/// and the screen shown is the [`crate::GameState`] state.
+/// Other systems can react to the story with its events, like [`crate::PageEntered`] or
+/// [`crate::RunFinished`].
+#[derive(Default)]
+pub struct StoryPlugin {
+    pub assets: StoryAssets,
+}
+
+impl Plugin for StoryPlugin {
+    fn build(&self, app: &mut App) {
+        events::add_events(app);
+        app.add_plugin(TextAssetPlugin)
+            .add_plugin(FluentAssetPlugin)
+            .insert_resource(self.assets.clone())
+            .add_startup_system(text_system::setup)
+            .add_system(scroll::scroll)
+            // After all the state transitions of the frame, which can raise events
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                events::send_events.label(StorySystem::SendEvents),
+            );
+        state::add_systems(app, CoreStage::Update);
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+/// Distance scrolled per key press or mouse wheel line, in logical pixels.
+const SCROLL_STEP: f32 = 40.;
+
+/// Scrollable content node, child of a clipping viewport node.
+#[derive(Component, Default)]
+pub struct ScrollContent {
+    /// Current scroll offset from the top of the content.
+    pub offset: f32,
+}
+
+/// Indicator shown when more content is available in the given direction.
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum ScrollIndicator {
+    Up,
+    Down,
+}
+
+/// Scroll the content with the keyboard arrows, page keys and the mouse wheel, and update
+/// the visibility of the scroll indicators.
+pub fn scroll(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut content_query: Query<(&mut ScrollContent, &mut Style, &Node, &Parent)>,
+    viewport_query: Query<&Node, Without<ScrollContent>>,
+    mut indicator_query: Query<(&ScrollIndicator, &mut Visibility)>,
+) {
+    let mut base_delta = 0.;
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        base_delta += SCROLL_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        base_delta -= SCROLL_STEP;
+    }
+    for event in mouse_wheel.iter() {
+        base_delta -= match event.unit {
+            MouseScrollUnit::Line => event.y * SCROLL_STEP,
+            MouseScrollUnit::Pixel => event.y,
+        };
+    }
+
+    for (mut content, mut style, node, parent) in content_query.iter_mut() {
+        let viewport_height = if let Ok(viewport) = viewport_query.get(parent.0) {
+            viewport.size.y
+        } else {
+            continue;
+        };
+        let mut delta = base_delta;
+        if keyboard_input.just_pressed(KeyCode::PageDown) {
+            delta += viewport_height;
+        }
+        if keyboard_input.just_pressed(KeyCode::PageUp) {
+            delta -= viewport_height;
+        }
+
+        let max_offset = (node.size.y - viewport_height).max(0.);
+        let offset = (content.offset + delta).clamp(0., max_offset);
+        if (offset - content.offset).abs() > f32::EPSILON {
+            content.offset = offset;
+            // Moving the node towards the flex top moves it up (Bevy is upside down)
+            style.position.top = Val::Px(offset);
+        }
+
+        for (indicator, mut visibility) in indicator_query.iter_mut() {
+            let is_visible = match indicator {
+                ScrollIndicator::Up => offset > 0.,
+                ScrollIndicator::Down => offset < max_offset,
+            };
+            if visibility.is_visible != is_visible {
+                visibility.is_visible = is_visible;
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use bevy::ecs::schedule::StateError;
+use bevy::prelude::*;
+
+use crate::plugin::StorySystem;
+use crate::text_asset::TextAsset;
+use crate::text_system::{self, TextSystem};
+
+/// Label of the systems moving through the story, which can change the game state.
+const NAVIGATE: &str = "navigate";
+
+/// Screen of the game, driving which content is shown and which keys are handled.
+///
+/// [`GameState::Paused`] and [`GameState::Settings`] are pushed over the screen they are
+/// opened from, which is kept as is under their menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    /// Waiting for the book to load.
+    Loading,
+    /// First page of the story, with the language picker.
+    Title,
+    /// Any other page of the story.
+    Reading,
+    /// Scores of the last runs, shown at the end of each run.
+    Scoreboard,
+    /// Pause menu.
+    Paused,
+    /// Settings menu, opened from the pause menu.
+    Settings,
+}
+
+/// Get the screen shown under the menus, at the bottom of the state stack.
+pub(crate) fn screen(state: &State<GameState>) -> &GameState {
+    state.inactives().first().unwrap_or_else(|| state.current())
+}
+
+/// Ignore a state transition which can't be scheduled, like a second transition during
+/// the same frame from keys pressed together.
+fn ignore_conflict(result: Result<(), StateError>) {
+    if let Err(err) = result {
+        debug!("State transition ignored: {}", err);
+    }
+}
+
+/// Add the game state and the systems of the story to a stage.
+pub(crate) fn add_systems(app: &mut App, stage: impl StageLabel + Clone) {
+    app.add_state_to_stage(stage.clone(), GameState::Loading)
+        .add_system_to_stage(
+            stage.clone(),
+            text_system::update.label(StorySystem::Update),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_update(GameState::Loading)
+                .label(StorySystem::Update)
+                .with_system(load),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_enter(GameState::Title)
+                .label(StorySystem::Update)
+                .with_system(show_page),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_update(GameState::Title)
+                .label(StorySystem::Update)
+                .with_system(pick_language)
+                .with_system(read.label(NAVIGATE))
+                .with_system(pause.after(NAVIGATE)),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_enter(GameState::Reading)
+                .label(StorySystem::Update)
+                .with_system(show_page),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_update(GameState::Reading)
+                .label(StorySystem::Update)
+                .with_system(read.label(NAVIGATE))
+                .with_system(pause.after(NAVIGATE)),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_enter(GameState::Scoreboard)
+                .label(StorySystem::Update)
+                .with_system(end_run),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_update(GameState::Scoreboard)
+                .label(StorySystem::Update)
+                .with_system(restart.label(NAVIGATE))
+                .with_system(pause.after(NAVIGATE)),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_exit(GameState::Scoreboard)
+                .label(StorySystem::Update)
+                .with_system(clear_scoreboard),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_enter(GameState::Paused)
+                .label(StorySystem::Update)
+                .with_system(spawn_pause_menu),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_resume(GameState::Paused)
+                .label(StorySystem::Update)
+                .with_system(spawn_pause_menu),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_update(GameState::Paused)
+                .label(StorySystem::Update)
+                .with_system(pause_menu),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_exit(GameState::Paused)
+                .label(StorySystem::Update)
+                .with_system(clear_menu),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_enter(GameState::Settings)
+                .label(StorySystem::Update)
+                .with_system(spawn_settings_menu),
+        )
+        .add_system_set_to_stage(
+            stage.clone(),
+            SystemSet::on_update(GameState::Settings)
+                .label(StorySystem::Update)
+                .with_system(pick_language)
+                .with_system(settings_menu),
+        )
+        .add_system_set_to_stage(
+            stage,
+            SystemSet::on_exit(GameState::Settings)
+                .label(StorySystem::Update)
+                .with_system(clear_menu),
+        );
+}
+
+/// Load the story, and show its title page once loaded.
+fn load(
+    mut commands: Commands,
+    text_assets: Res<Assets<TextAsset>>,
+    asset_server: Res<AssetServer>,
+    mut text_system: ResMut<TextSystem>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if text_system.load(&mut commands, &text_assets, &asset_server) {
+        ignore_conflict(state.set(GameState::Title));
+    }
+}
+
+/// Show the current page, when entering the title or another page.
+fn show_page(
+    mut commands: Commands,
+    fonts: Res<Assets<Font>>,
+    mut text_system: ResMut<TextSystem>,
+) {
+    text_system.show_page(&mut commands, &fonts);
+}
+
+/// Change the language with the left and right arrows, if the book has translations.
+fn pick_language(
+    mut commands: Commands,
+    fonts: Res<Assets<Font>>,
+    mut text_system: ResMut<TextSystem>,
+    keyboard_input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+) {
+    if !text_system.localization.has_translations() {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        text_system.cycle_language(&mut commands, &fonts, -1, &state);
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        text_system.cycle_language(&mut commands, &fonts, 1, &state);
+    }
+}
+
+/// Handle the buttons of the current page, and move to the state of the page they lead
+/// to: the title page, another page, or the scoreboard at the end of the run.
+fn read(
+    mut commands: Commands,
+    fonts: Res<Assets<Font>>,
+    mut text_system: ResMut<TextSystem>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !text_system.read(&mut commands, &fonts, &mut keyboard_input) {
+        return;
+    }
+    let next = if text_system.has_ended() {
+        GameState::Scoreboard
+    } else if text_system.is_first_page() {
+        GameState::Title
+    } else {
+        GameState::Reading
+    };
+    if *state.current() == next {
+        text_system.show_page(&mut commands, &fonts);
+    } else {
+        ignore_conflict(state.set(next));
+    }
+}
+
+/// Record the score of the run and show the scoreboard.
+fn end_run(mut commands: Commands, fonts: Res<Assets<Font>>, mut text_system: ResMut<TextSystem>) {
+    text_system.end_run(&mut commands, &fonts);
+}
+
+/// Start a new run from the scoreboard with the space key.
+fn restart(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keyboard_input.clear_just_pressed(KeyCode::Space) {
+        trace!("space");
+        ignore_conflict(state.set(GameState::Title));
+    }
+}
+
+/// Clear the scoreboard and restart the story, when leaving the scoreboard.
+fn clear_scoreboard(mut commands: Commands, mut text_system: ResMut<TextSystem>) {
+    text_system.restart(&mut commands);
+}
+
+/// Open the pause menu with the P key.
+fn pause(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keyboard_input.clear_just_pressed(KeyCode::P) {
+        // Ignored if a button already moved to another state during this frame
+        ignore_conflict(state.push(GameState::Paused));
+    }
+}
+
+/// Close the pause menu with the P key, or open the settings with the S key.
+fn pause_menu(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keyboard_input.clear_just_pressed(KeyCode::P) {
+        ignore_conflict(state.pop());
+    } else if keyboard_input.clear_just_pressed(KeyCode::S) {
+        ignore_conflict(state.push(GameState::Settings));
+    }
+}
+
+/// Close the settings with the S key, back to the pause menu.
+fn settings_menu(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keyboard_input.clear_just_pressed(KeyCode::S) {
+        ignore_conflict(state.pop());
+    }
+}
+
+fn spawn_pause_menu(
+    mut commands: Commands,
+    fonts: Res<Assets<Font>>,
+    mut text_system: ResMut<TextSystem>,
+) {
+    text_system.spawn_pause_menu(&mut commands, &fonts);
+}
+
+fn spawn_settings_menu(
+    mut commands: Commands,
+    fonts: Res<Assets<Font>>,
+    mut text_system: ResMut<TextSystem>,
+) {
+    text_system.spawn_settings_menu(&mut commands, &fonts);
+}
+
+fn clear_menu(mut commands: Commands, mut text_system: ResMut<TextSystem>) {
+    text_system.clear_overlay(&mut commands);
+}
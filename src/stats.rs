@@ -0,0 +1,278 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::book::*;
+use crate::paths;
+
+/// Average silent reading speed, in words per minute, to estimate reading times.
+const WORDS_PER_MINUTE: f32 = 200.;
+
+/// Output format of the statistics of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatsFormat {
+    /// Human-readable text report.
+    Text,
+    /// JSON document, for other tools.
+    Json,
+}
+
+impl StatsFormat {
+    /// Get a format from its name on the command line.
+    pub fn from_name(name: &str) -> Option<StatsFormat> {
+        match name {
+            "text" => Some(StatsFormat::Text),
+            "json" => Some(StatsFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Statistics of a single page.
+#[derive(Serialize)]
+pub struct PageStats {
+    /// Page key, its name or index.
+    pub page: String,
+    /// Number of words of the lines and own buttons of the page.
+    pub words: usize,
+    /// Estimated reading time, in seconds.
+    pub reading_seconds: f32,
+    /// Number of lines of text.
+    pub lines: usize,
+    /// Number of buttons, including the default buttons the page shows.
+    pub buttons: usize,
+}
+
+/// Longest line of a book.
+#[derive(Serialize)]
+pub struct LongestLine {
+    /// Key of the page of the line.
+    pub page: String,
+    /// Index of the line in its page.
+    pub line_index: usize,
+    /// Length of the line, in characters.
+    pub chars: usize,
+    pub text: String,
+}
+
+/// Number of playthroughs reaching an ending.
+#[derive(Serialize)]
+pub struct EndingStats {
+    /// Key of the page the run ends from.
+    pub page: String,
+    /// Number of distinct paths to the ending, without revisiting any page.
+    pub paths: usize,
+    pub min_pages_read: u32,
+    pub max_pages_read: u32,
+}
+
+/// Statistics of a whole book, to budget writing time and spot padded pages.
+#[derive(Serialize)]
+pub struct BookStats {
+    pub pages: Vec<PageStats>,
+    /// Number of words of the whole book, including the default buttons once.
+    pub words: usize,
+    /// Estimated time to read the whole book once, in minutes.
+    pub reading_minutes: f32,
+    /// Number of pages offering more than one button.
+    pub choices: usize,
+    /// Average number of buttons of the non-final pages.
+    pub branching_factor: f32,
+    pub longest_line: Option<LongestLine>,
+    pub endings: Vec<EndingStats>,
+}
+
+impl fmt::Display for BookStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} page(s), {} word(s), about {:.1} minute(s) of reading",
+            self.pages.len(),
+            self.words,
+            self.reading_minutes
+        )?;
+        writeln!(
+            f,
+            "{} choice(s), branching factor {:.2}",
+            self.choices, self.branching_factor
+        )?;
+        if let Some(line) = &self.longest_line {
+            writeln!(
+                f,
+                "longest line: {} chars, page '{}' line {}: {}",
+                line.chars, line.page, line.line_index, line.text
+            )?;
+        }
+        writeln!(f, "pages:")?;
+        for page in &self.pages {
+            writeln!(
+                f,
+                "- '{}': {} word(s), {:.0}s, {} line(s), {} button(s)",
+                page.page, page.words, page.reading_seconds, page.lines, page.buttons
+            )?;
+        }
+        writeln!(f, "endings:")?;
+        for ending in &self.endings {
+            writeln!(
+                f,
+                "- '{}': {} path(s), {} to {} pages read",
+                ending.page, ending.paths, ending.min_pages_read, ending.max_pages_read
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Count the words of a text, as runs of characters separated by whitespace.
+fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Compute the statistics of a book, in the source language.
+pub fn compute(book: &Book) -> BookStats {
+    let mut pages = vec![];
+    let mut longest_line: Option<LongestLine> = None;
+    for (page_index, page) in book.pages.iter().enumerate() {
+        let page_key = book.page_key(page_index);
+        let line_words: usize = page.lines.iter().map(|line| word_count(&line.text)).sum();
+        let button_words: usize = page
+            .buttons
+            .iter()
+            .flatten()
+            .map(|(_, button)| word_count(&button.text))
+            .sum();
+        let words = line_words + button_words;
+        for (line_index, line) in page.lines.iter().enumerate() {
+            let chars = line.text.chars().count();
+            if longest_line
+                .as_ref()
+                .is_none_or(|longest| chars > longest.chars)
+            {
+                longest_line = Some(LongestLine {
+                    page: page_key.clone(),
+                    line_index,
+                    chars,
+                    text: line.text.clone(),
+                });
+            }
+        }
+        pages.push(PageStats {
+            page: page_key,
+            words,
+            reading_seconds: words as f32 / WORDS_PER_MINUTE * 60.,
+            lines: page.lines.len(),
+            buttons: page.buttons.as_ref().unwrap_or(&book.default_buttons).len(),
+        });
+    }
+
+    let default_words: usize = book
+        .default_buttons
+        .values()
+        .map(|button| word_count(&button.text))
+        .sum();
+    let words = pages.iter().map(|page| page.words).sum::<usize>() + default_words;
+    let choices = pages.iter().filter(|page| page.buttons > 1).count();
+    let branching: Vec<usize> = book
+        .pages
+        .iter()
+        .zip(&pages)
+        .filter(|(page, _)| !page.is_final)
+        .map(|(_, stats)| stats.buttons)
+        .collect();
+    let branching_factor = if branching.is_empty() {
+        0.
+    } else {
+        branching.iter().sum::<usize>() as f32 / branching.len() as f32
+    };
+
+    let report = paths::analyze(book);
+    let endings = report
+        .endings
+        .iter()
+        .map(|ending| EndingStats {
+            page: book.page_key(ending.page_index),
+            paths: ending.path_count,
+            min_pages_read: ending.min_pages_read,
+            max_pages_read: ending.max_pages_read,
+        })
+        .collect();
+
+    BookStats {
+        pages,
+        words,
+        reading_minutes: words as f32 / WORDS_PER_MINUTE,
+        choices,
+        branching_factor,
+        longest_line,
+        endings,
+    }
+}
+
+/// Render the statistics of a book in the given format.
+pub fn export(book: &Book, format: StatsFormat) -> String {
+    let stats = compute(book);
+    match format {
+        StatsFormat::Text => stats.to_string(),
+        StatsFormat::Json => serde_json::to_string_pretty(&stats).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::tests::sample_book;
+
+    #[test]
+    fn computes_stats() {
+        let stats = compute(&sample_book());
+        let pages: Vec<_> = stats
+            .pages
+            .iter()
+            .map(|page| (page.page.as_str(), page.words, page.lines, page.buttons))
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                ("start", 15, 1, 3),
+                ("hall", 10, 1, 3),
+                ("exit", 3, 1, 1),
+                ("cellar", 3, 1, 0),
+                ("secret", 4, 1, 1),
+            ]
+        );
+        assert_eq!(stats.pages[0].reading_seconds, 4.5);
+        assert_eq!(stats.words, 35);
+        assert_eq!(stats.reading_minutes, 0.175);
+        assert_eq!(stats.choices, 2);
+        // Buttons of the non-final pages: 3, 3, 0 and 1
+        assert_eq!(stats.branching_factor, 1.75);
+
+        let line = stats.longest_line.as_ref().unwrap();
+        assert_eq!((line.page.as_str(), line.line_index), ("start", 0));
+        assert_eq!(line.chars, 27);
+
+        let endings: Vec<_> = stats
+            .endings
+            .iter()
+            .map(|e| (e.page.as_str(), e.paths, e.min_pages_read, e.max_pages_read))
+            .collect();
+        assert_eq!(endings, vec![("start", 1, 1, 1), ("exit", 2, 2, 3)]);
+    }
+
+    #[test]
+    fn exports_formats() {
+        let book = sample_book();
+        let text = export(&book, StatsFormat::Text);
+        assert!(text.starts_with("5 page(s), 35 word(s), about 0.2 minute(s) of reading\n"));
+        assert!(
+            text.contains("- 'exit': 2 path(s), 2 to 3 pages read\n"),
+            "{}",
+            text
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&export(&book, StatsFormat::Json)).unwrap();
+        assert_eq!(json["words"], 35);
+        assert_eq!(json["pages"][3]["page"], "cellar");
+        assert_eq!(StatsFormat::from_name("yaml"), None);
+    }
+}
@@ -0,0 +1,358 @@
+use bevy::log::{error, warn};
+use std::collections::HashMap;
+
+use crate::book::*;
+use crate::ink::InkStory;
+
+/// Source of the pages of a story, and of the navigation between them.
+///
+/// The game only talks to the story through this trait, so it can run the pages of a
+/// [`Book`] as well as a story generated on the fly by a narrative scripting runtime.
+pub trait StoryBackend: Send + Sync {
+    /// Get the book holding the presentation settings of the story, like its styles,
+    /// fonts, and languages.
+    fn book(&self) -> &Book;
+
+    /// Restart the story from its beginning.
+    fn restart(&mut self);
+
+    /// Get the current page, if any.
+    fn current_page(&self) -> Option<&Page>;
+
+    /// Get the key of the current page, to derive the translation identifiers of its
+    /// lines and buttons.
+    fn page_key(&self) -> String;
+
+    /// Is the current page the first page of the story?
+    fn is_first_page(&self) -> bool;
+
+    /// Has the story ended, with the run to be scored?
+    fn has_ended(&self) -> bool;
+
+    /// Activate the button `key` of the current page, moving the story forward, and
+    /// return whether the story moved to a page or ended.
+    fn activate(&mut self, key: &str) -> bool;
+}
+
+/// Load a story from its source text, with the backend matching its format.
+pub fn load(source: &str, format: BookFormat) -> Result<Box<dyn StoryBackend>, String> {
+    match format {
+        BookFormat::Ink => Ok(Box::new(InkBackend::new(InkStory::from_json(source)?)?)),
+        _ => Ok(Box::new(BookBackend::new(Book::parse(source, format)?))),
+    }
+}
+
+/// Story backend running the pages of a [`Book`].
+pub struct BookBackend {
+    book: Book,
+    page_index: usize,
+    has_ended: bool,
+}
+
+impl BookBackend {
+    /// Create a backend starting on the first page of a book.
+    pub fn new(book: Book) -> Self {
+        BookBackend {
+            book,
+            page_index: 0,
+            has_ended: false,
+        }
+    }
+}
+
+impl StoryBackend for BookBackend {
+    fn book(&self) -> &Book {
+        &self.book
+    }
+
+    fn restart(&mut self) {
+        self.page_index = 0;
+        self.has_ended = false;
+    }
+
+    fn current_page(&self) -> Option<&Page> {
+        self.book.pages.get(self.page_index)
+    }
+
+    fn page_key(&self) -> String {
+        self.book.page_key(self.page_index)
+    }
+
+    fn is_first_page(&self) -> bool {
+        self.page_index == 0
+    }
+
+    fn has_ended(&self) -> bool {
+        self.has_ended
+    }
+
+    fn activate(&mut self, key: &str) -> bool {
+        let page = match self.current_page() {
+            Some(page) => page,
+            None => return false,
+        };
+        let buttons = page.buttons.as_ref().unwrap_or(&self.book.default_buttons);
+        let action = match buttons.get(key) {
+            Some(button) => &button.action,
+            None => return false,
+        };
+
+        match self.book.destination(self.page_index, action) {
+            Destination::Page(page_index) => self.page_index = page_index,
+            Destination::End => self.has_ended = true,
+            Destination::Missing => {
+                if let ButtonAction::JumpToPage(page_name) = action {
+                    warn!("Unknown page '{}'", page_name);
+                }
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Story backend running a compiled Ink story.
+///
+/// Each stop of the story to wait for a choice from the player becomes a page, named
+/// after the knot and stitch it stopped in. Its text rows become lines, and its choices
+/// buttons: a single choice uses the space key, and multiple choices the number keys.
+/// Once the story runs out of choices, its last page is final.
+pub struct InkBackend {
+    story: InkStory,
+    /// Presentation settings, with the default buttons of the final page.
+    book: Book,
+    page: Page,
+    page_count: usize,
+    has_ended: bool,
+}
+
+impl InkBackend {
+    /// Create a backend starting at the beginning of an Ink story.
+    pub fn new(story: InkStory) -> Result<Self, String> {
+        let mut book = Book::default();
+        book.default_buttons.insert(
+            "space".to_string(),
+            Button {
+                id: None,
+                text: "Continue".to_string(),
+                action: ButtonAction::NextPage,
+                style: None,
+            },
+        );
+        let mut backend = InkBackend {
+            story,
+            book,
+            page: Page::default(),
+            page_count: 0,
+            has_ended: false,
+        };
+        backend.next_page()?;
+        Ok(backend)
+    }
+
+    /// Run the story to its next stop, and build the page of its content.
+    fn next_page(&mut self) -> Result<(), String> {
+        let text = self.story.continue_maximally()?;
+        let lines = text
+            .lines()
+            .map(|row| Line {
+                text: row.to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        let choices: Vec<String> = self.story.choices().map(|c| c.text.clone()).collect();
+        if choices.len() > CHOICE_KEYS.len() {
+            warn!(
+                "Only the first {} choices are shown, out of {}",
+                CHOICE_KEYS.len(),
+                choices.len()
+            );
+        }
+        let is_single_choice = choices.len() == 1;
+        let buttons: HashMap<String, Button> = choices
+            .into_iter()
+            .zip(CHOICE_KEYS)
+            .map(|(text, key)| {
+                let key = if is_single_choice { "space" } else { key };
+                let button = Button {
+                    id: None,
+                    text,
+                    action: ButtonAction::NextPage,
+                    style: None,
+                };
+                (key.to_string(), button)
+            })
+            .collect();
+
+        let path = self.story.current_path();
+        self.page = Page {
+            name: if path.is_empty() { None } else { Some(path) },
+            is_final: buttons.is_empty(),
+            lines,
+            buttons: if buttons.is_empty() {
+                None
+            } else {
+                Some(buttons)
+            },
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    /// Log an error of the story, and end it.
+    fn fail(&mut self, error: String) {
+        error!("Ink story error: {}", error);
+        self.has_ended = true;
+    }
+}
+
+impl StoryBackend for InkBackend {
+    fn book(&self) -> &Book {
+        &self.book
+    }
+
+    fn restart(&mut self) {
+        self.page_count = 0;
+        self.has_ended = false;
+        if let Err(error) = self.story.reset().and_then(|_| self.next_page()) {
+            self.fail(error);
+        }
+    }
+
+    fn current_page(&self) -> Option<&Page> {
+        Some(&self.page)
+    }
+
+    fn page_key(&self) -> String {
+        self.page
+            .name
+            .clone()
+            .unwrap_or_else(|| self.page_count.to_string())
+    }
+
+    fn is_first_page(&self) -> bool {
+        self.page_count == 0
+    }
+
+    fn has_ended(&self) -> bool {
+        self.has_ended
+    }
+
+    fn activate(&mut self, key: &str) -> bool {
+        if self.page.is_final {
+            self.has_ended = true;
+            return true;
+        }
+        // Only the keys of the shown buttons pick a choice
+        match &self.page.buttons {
+            Some(buttons) if buttons.contains_key(key) => {}
+            _ => return false,
+        }
+        let index = CHOICE_KEYS.iter().position(|k| *k == key).unwrap_or(0);
+        self.page_count += 1;
+        if let Err(error) = self
+            .story
+            .choose_visible(index)
+            .and_then(|_| self.next_page())
+        {
+            self.fail(error);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the buttons of the current page, as sorted key and text pairs.
+    fn buttons(story: &dyn StoryBackend) -> Vec<(String, String)> {
+        let mut buttons: Vec<_> = story
+            .current_page()
+            .and_then(|page| page.buttons.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|(key, button)| (key.clone(), button.text.clone()))
+            .collect();
+        buttons.sort();
+        buttons
+    }
+
+    fn lines(story: &dyn StoryBackend) -> Vec<String> {
+        let page = story.current_page().unwrap();
+        page.lines.iter().map(|line| line.text.clone()).collect()
+    }
+
+    fn pair(key: &str, text: &str) -> (String, String) {
+        (key.to_string(), text.to_string())
+    }
+
+    #[test]
+    fn ink_choices_become_buttons() {
+        let source = include_str!("fixtures/ink/choices.ink.json");
+        let mut story = load(source, BookFormat::Ink).unwrap();
+        assert!(story.is_first_page());
+        assert_eq!(story.page_key(), "hub");
+        assert_eq!(
+            lines(&*story),
+            vec!["You have 0 gold. You came here 1 times."]
+        );
+        assert_eq!(
+            buttons(&*story),
+            vec![pair("1", "Take the gold"), pair("2", "Leave")]
+        );
+
+        // Keys without a choice are ignored
+        assert!(!story.activate("3"));
+        assert!(!story.activate("space"));
+        assert!(story.is_first_page());
+
+        assert!(story.activate("1"));
+        assert!(!story.is_first_page());
+        assert_eq!(
+            lines(&*story),
+            vec!["You have 10 gold. You came here 2 times."]
+        );
+        assert_eq!(
+            buttons(&*story),
+            vec![pair("1", "Spend the gold"), pair("2", "Leave")]
+        );
+
+        assert!(story.activate("1"));
+        assert_eq!(story.page_key(), "shop");
+        assert!(story.current_page().unwrap().is_final);
+        assert!(buttons(&*story).is_empty());
+        assert!(!story.has_ended());
+        assert!(story.activate("space"));
+        assert!(story.has_ended());
+
+        story.restart();
+        assert!(story.is_first_page());
+        assert!(!story.has_ended());
+        assert_eq!(
+            lines(&*story),
+            vec!["You have 0 gold. You came here 1 times."]
+        );
+    }
+
+    #[test]
+    fn ink_single_choice_uses_space() {
+        let source = include_str!("fixtures/ink/basics.ink.json");
+        let mut story = load(source, BookFormat::Ink).unwrap();
+        // Pages without knot are keyed by their index
+        assert_eq!(story.page_key(), "0");
+        assert_eq!(lines(&*story), vec!["Hello world."]);
+        assert_eq!(buttons(&*story), vec![pair("space", "Enter the forest")]);
+
+        assert!(!story.activate("1"));
+        assert!(story.is_first_page());
+        assert!(story.activate("space"));
+        assert_eq!(story.page_key(), "forest.clearing");
+        assert_eq!(
+            lines(&*story),
+            vec!["Trees everywhere.", "A quiet clearing."]
+        );
+        assert!(story.current_page().unwrap().is_final);
+    }
+}
@@ -10,9 +10,12 @@ use bevy::{
 #[uuid = "08588ad8-7dda-46bf-8857-1e896e4264f5"]
 pub struct TextAsset {
     pub value: String,
+    /// Extension of the file the asset was loaded from, to tell apart its format.
+    pub extension: String,
 }
 
-/// Asset loader for deserializing `*.txt` / `*.json` into a [`TextAsset`].
+/// Asset loader for deserializing `*.txt` / `*.json` / `*.ron` / `*.yaml` / `*.twee` /
+/// `*.md` into a [`TextAsset`].
 #[derive(Default)]
 struct TextAssetLoader;
 
@@ -24,15 +27,22 @@ impl AssetLoader for TextAssetLoader {
     ) -> BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
         Box::pin(async move {
             let s = std::str::from_utf8(bytes)?;
+            let extension = load_context
+                .path()
+                .extension()
+                .map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase());
             load_context.set_default_asset(LoadedAsset::new(TextAsset {
                 value: s.to_owned(),
+                extension,
             }));
             Ok(())
         })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["txt", "json"]
+        &[
+            "txt", "json", "ron", "yaml", "yml", "twee", "tw", "md", "markdown",
+        ]
     }
 }
 
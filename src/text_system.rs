@@ -0,0 +1,1486 @@
+use bevy::{
+    ecs::system::EntityCommands,
+    prelude::*,
+    ui::widget::ImageMode,
+    window::{WindowId, WindowResized},
+};
+use chrono::prelude::*;
+use fluent_bundle::FluentValue;
+use std::{borrow::Cow, collections::HashMap, ops::Range};
+
+use crate::book::*;
+use crate::events::StoryEvent;
+use crate::fluent_asset::FluentAsset;
+use crate::fonts::BookFonts;
+use crate::localization::Localization;
+use crate::plugin::StoryAssets;
+use crate::scroll::{ScrollContent, ScrollIndicator};
+use crate::state::{self as game_state, GameState};
+use crate::story::{self, StoryBackend};
+use crate::text_asset::TextAsset;
+use crate::{bidi, layout};
+
+/// Height of a button, in design pixels.
+const BUTTON_HEIGHT: f32 = 64.;
+
+/// Color of the background of the menus, dimming the content under them.
+const MENU_COLOR: Color = Color::rgba(0., 0., 0., 0.8);
+
+/// Keyboard keys activating the buttons of a page, by button name.
+pub(crate) const BUTTON_KEYS: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Space),
+    ("y", KeyCode::Y),
+    ("n", KeyCode::N),
+    ("m", KeyCode::M),
+    ("1", KeyCode::Key1),
+    ("2", KeyCode::Key2),
+    ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5),
+    ("6", KeyCode::Key6),
+    ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8),
+    ("9", KeyCode::Key9),
+];
+
+#[derive(Component, Default)]
+struct Background;
+
+/// Item of a page, with everything its entities are spawned from, to reuse them when
+/// the next screen shows the same item.
+#[derive(PartialEq)]
+enum PageItem {
+    Line {
+        index: usize,
+        text: String,
+        style: ResolvedStyle,
+        text_width: Option<f32>,
+    },
+    Button {
+        text: String,
+        style: ResolvedStyle,
+        image: Handle<Image>,
+    },
+    LanguagePicker {
+        text: String,
+        style: ResolvedStyle,
+    },
+}
+
+/// Look of the nodes framing the items of a page, which are reused only by the pages
+/// with the same look.
+#[derive(PartialEq)]
+struct PageFrame {
+    background_color: Color,
+    align: Option<JustifyContent>,
+}
+
+/// Entities of the page currently shown, to update them in place for the next screen.
+struct PageNodes {
+    frame: PageFrame,
+    /// Parent of the items: the content node, or the scroll node with
+    /// [`OverflowMode::Scroll`].
+    container: Entity,
+    /// Items of the screen in display order, with their entity.
+    items: Vec<(PageItem, Entity)>,
+}
+
+/// Score of a finished run.
+#[derive(Copy, Clone, Debug)]
+pub struct Score {
+    /// Date the run finished.
+    pub date: DateTime<Utc>,
+    /// Number of pages read during the run.
+    pub page_read: u32,
+}
+
+/// State of the story shown, and of the UI displaying it.
+pub struct TextSystem {
+    pub(crate) story: Option<Box<dyn StoryBackend>>,
+    content_handle: Handle<TextAsset>,
+    /// Error of the book if it failed to load, to report it only once.
+    pub(crate) load_error: Option<String>,
+    book_fonts: BookFonts,
+    pub(crate) localization: Localization,
+    pub(crate) root_node: Option<Entity>,
+    /// Index of the continuation screen of the current page, with
+    /// [`OverflowMode::Paginate`].
+    pub(crate) screen_index: usize,
+    /// Ranges of lines of each continuation screen of the current page.
+    pub(crate) screens: Vec<Range<usize>>,
+    buttons: HashMap<String, Handle<Image>>,
+    pub(crate) page_read: u32,
+    scores: Vec<Score>,
+    /// Root node of the menu shown over the current content, if any.
+    overlay_node: Option<Entity>,
+    /// Entities of the page currently shown, if any.
+    page_nodes: Option<PageNodes>,
+    /// Logical size of the window.
+    window_size: Vec2,
+    /// Scale from design pixels to logical window pixels.
+    ui_scale: f32,
+    /// User multiplier applied on top of [`TextSystem::ui_scale`] for font sizes.
+    font_scale: f32,
+    /// Source of the current date, to record scores.
+    pub(crate) clock: fn() -> DateTime<Utc>,
+    /// Events raised since the last time they were sent.
+    events: Vec<StoryEvent>,
+}
+
+impl Default for TextSystem {
+    fn default() -> Self {
+        TextSystem {
+            story: None,
+            content_handle: Default::default(),
+            load_error: None,
+            book_fonts: Default::default(),
+            localization: Default::default(),
+            root_node: None,
+            screen_index: 0,
+            screens: vec![],
+            buttons: HashMap::default(),
+            page_read: 0,
+            scores: vec![],
+            overlay_node: None,
+            page_nodes: None,
+            window_size: Vec2::new(1280., 720.),
+            ui_scale: 1.,
+            font_scale: 1.,
+            clock: Utc::now,
+            events: vec![],
+        }
+    }
+}
+
+impl TextSystem {
+    /// Initialize a new instance.
+    pub(crate) fn new(
+        content_handle: Handle<TextAsset>,
+        font: Handle<Font>,
+        buttons: HashMap<String, Handle<Image>>,
+        window_size: Vec2,
+    ) -> Self {
+        let mut text_system = TextSystem {
+            book_fonts: BookFonts::new(font),
+            content_handle,
+            buttons,
+            window_size,
+            ..Default::default()
+        };
+        text_system.update_scale();
+        text_system
+    }
+
+    /// Get the book of the current story, if loaded.
+    pub fn book(&self) -> Option<&Book> {
+        self.story.as_ref().map(|story| story.book())
+    }
+
+    /// Recompute the UI scale from the window size and the book design resolution.
+    fn update_scale(&mut self) {
+        let (resolution, scale_mode) = if let Some(book) = self.book() {
+            (book.design_resolution, book.scale_mode)
+        } else {
+            (Resolution::default(), ScaleMode::default())
+        };
+        let scale_x = self.window_size.x / resolution.width;
+        let scale_y = self.window_size.y / resolution.height;
+        self.ui_scale = match scale_mode {
+            ScaleMode::Fit | ScaleMode::Expand => scale_x.min(scale_y),
+            ScaleMode::MatchWidth => scale_x,
+            ScaleMode::MatchHeight => scale_y,
+        }
+        .max(f32::EPSILON);
+    }
+
+    /// Handle a change of the window size.
+    fn resize(
+        &mut self,
+        commands: &mut Commands,
+        fonts: &Assets<Font>,
+        window_size: Vec2,
+        state: &State<GameState>,
+    ) {
+        self.window_size = window_size;
+        self.update_scale();
+        self.refresh(commands, fonts, state);
+    }
+
+    /// Change the user font scale multiplier.
+    fn set_font_scale(
+        &mut self,
+        commands: &mut Commands,
+        fonts: &Assets<Font>,
+        font_scale: f32,
+        state: &State<GameState>,
+    ) {
+        self.font_scale = font_scale.clamp(0.5, 2.0);
+        self.refresh(commands, fonts, state);
+    }
+
+    /// Change the language by `offset` positions in the languages of the book.
+    pub(crate) fn cycle_language(
+        &mut self,
+        commands: &mut Commands,
+        fonts: &Assets<Font>,
+        offset: isize,
+        state: &State<GameState>,
+    ) {
+        self.localization.cycle(offset);
+        self.refresh(commands, fonts, state);
+    }
+
+    /// Respawn the content currently displayed for a game state, if any, and the menu
+    /// over it.
+    fn refresh(&mut self, commands: &mut Commands, fonts: &Assets<Font>, state: &State<GameState>) {
+        match game_state::screen(state) {
+            GameState::Scoreboard => self.spawn_leaderboard(commands, fonts),
+            GameState::Title | GameState::Reading if self.current_page().is_some() => {
+                // Sizes and strings changed, so nothing can be reused
+                self.clear(commands);
+                self.setup_page(commands, fonts);
+            }
+            _ => {}
+        }
+        match state.current() {
+            GameState::Paused => self.spawn_pause_menu(commands, fonts),
+            GameState::Settings => self.spawn_settings_menu(commands, fonts),
+            _ => {}
+        }
+    }
+
+    /// Build a text with the fonts of a style, falling back to other fonts for the
+    /// characters the style font doesn't have.
+    ///
+    /// The text is in logical order, and is reordered for display according to the
+    /// direction of the current language. Each row separated by `\n` is reordered on its
+    /// own.
+    fn text(
+        &self,
+        value: &str,
+        style: &ResolvedStyle,
+        font_size: f32,
+        alignment: TextAlignment,
+        fonts: &Assets<Font>,
+    ) -> Text {
+        let value = bidi::reorder(value, self.localization.direction());
+        self.visual_text(&value, style, font_size, alignment, fonts)
+    }
+
+    /// Build a text already in visual order, like [`TextSystem::text`].
+    fn visual_text(
+        &self,
+        value: &str,
+        style: &ResolvedStyle,
+        font_size: f32,
+        alignment: TextAlignment,
+        fonts: &Assets<Font>,
+    ) -> Text {
+        Text {
+            sections: self.book_fonts.sections(value, style, font_size, fonts),
+            alignment,
+        }
+    }
+
+    /// Height of the content area, in design pixels.
+    fn content_height(&self) -> f32 {
+        match self.book() {
+            Some(book) if book.scale_mode == ScaleMode::Fit => book.design_resolution.height,
+            _ => self.window_size.y / self.ui_scale,
+        }
+    }
+
+    /// Convert a length in design pixels into a value in logical window pixels.
+    fn px(&self, value: f32) -> Val {
+        Val::Px(value * self.ui_scale)
+    }
+
+    /// Convert a font size in design pixels into a font size in logical window pixels,
+    /// including the user font scale.
+    fn font_size(&self, size: f32) -> f32 {
+        size * self.ui_scale * self.font_scale
+    }
+
+    /// Load the story once its text asset is loaded, and return whether it is loaded.
+    ///
+    /// A book failing to load is logged, and never loaded.
+    pub(crate) fn load(
+        &mut self,
+        commands: &mut Commands,
+        text_assets: &Assets<TextAsset>,
+        asset_server: &AssetServer,
+    ) -> bool {
+        if self.load_error.is_some() {
+            return false;
+        }
+        let content = match text_assets.get(self.content_handle.clone()) {
+            Some(content) => content,
+            None => return false,
+        };
+        let format =
+            BookFormat::detect(&content.extension, &content.value).unwrap_or(BookFormat::Json);
+        let story = match story::load(&content.value, format) {
+            Ok(story) => story,
+            Err(err) => {
+                error!("Failed to load book: {}", err);
+                self.load_error = Some(err);
+                return false;
+            }
+        };
+        self.clear(commands);
+        let book = story.book();
+        for error in book.validate() {
+            warn!("{}", error);
+        }
+        self.book_fonts.load(book, asset_server);
+        self.localization.load(book, asset_server);
+        self.story = Some(story);
+        self.update_scale();
+        self.screen_index = 0;
+        self.events.push(StoryEvent::BookLoaded);
+        true
+    }
+
+    /// Handle the buttons of the current page, and return whether the story moved to
+    /// another page or ended.
+    ///
+    /// The continuation screens of a paginated page are shown without leaving the page.
+    pub(crate) fn read(
+        &mut self,
+        commands: &mut Commands,
+        fonts: &Assets<Font>,
+        keyboard_input: &mut Input<KeyCode>,
+    ) -> bool {
+        if self.screen_index + 1 < self.screens.len() {
+            // Continuation screen of a paginated page
+            if keyboard_input.clear_just_pressed(KeyCode::Space) {
+                trace!("space");
+                self.screen_index += 1;
+                self.setup_page(commands, fonts);
+            }
+            return false;
+        }
+
+        let page = match self.current_page() {
+            Some(page) => page,
+            None => return false,
+        };
+        let buttons = if let Some(buttons) = &page.buttons {
+            buttons
+        } else {
+            &self.book().unwrap().default_buttons
+        };
+        let pressed = BUTTON_KEYS.iter().find(|(name, key_code)| {
+            buttons.contains_key(*name) && keyboard_input.just_pressed(*key_code)
+        });
+        let (name, key_code) = match pressed {
+            Some(pressed) => pressed,
+            None => return false,
+        };
+        // Don't let the systems of the next game state see the key again
+        keyboard_input.clear_just_pressed(*key_code);
+        trace!("{}", name);
+
+        // Buttons leading nowhere keep the current page, without reading it
+        let story = self.story.as_mut().unwrap();
+        let page_key = story.page_key();
+        if !story.activate(name) {
+            return false;
+        }
+        self.page_read += 1;
+        self.events
+            .push(StoryEvent::ChoiceMade(page_key.clone(), name.to_string()));
+        self.events.push(StoryEvent::PageLeft(page_key));
+        true
+    }
+
+    /// Show the current page from its first screen, if any.
+    pub(crate) fn show_page(&mut self, commands: &mut Commands, fonts: &Assets<Font>) {
+        self.screen_index = 0;
+        if self.current_page().is_some() {
+            self.setup_page(commands, fonts);
+            self.enter_page();
+        }
+    }
+
+    /// Clear the scoreboard and restart the story for a new run.
+    pub(crate) fn restart(&mut self, commands: &mut Commands) {
+        self.clear(commands);
+        if let Some(story) = &mut self.story {
+            story.restart();
+        }
+        self.screen_index = 0;
+        self.page_read = 0;
+    }
+
+    /// Raise the event of entering the current page.
+    fn enter_page(&mut self) {
+        if let Some(story) = &self.story {
+            self.events.push(StoryEvent::PageEntered(story.page_key()));
+        }
+    }
+
+    /// Take the events raised since the last call.
+    pub(crate) fn take_events(&mut self) -> Vec<StoryEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Get the current page, if any.
+    pub fn current_page(&self) -> Option<&Page> {
+        self.story.as_ref().and_then(|story| story.current_page())
+    }
+
+    /// Is the current page the first page of the story?
+    pub(crate) fn is_first_page(&self) -> bool {
+        self.story
+            .as_ref()
+            .is_some_and(|story| story.is_first_page())
+    }
+
+    /// Has the current run ended?
+    ///
+    /// A story without a current page has nothing more to show.
+    pub(crate) fn has_ended(&self) -> bool {
+        self.story
+            .as_ref()
+            .is_some_and(|story| story.has_ended() || story.current_page().is_none())
+    }
+
+    /// Clear all content.
+    fn clear(&mut self, commands: &mut Commands) {
+        if let Some(entity) = &self.root_node {
+            commands.entity(*entity).despawn_recursive();
+        }
+        self.root_node = None;
+        self.overlay_node = None;
+        self.page_nodes = None;
+    }
+
+    /// Setup the current page, reusing the entities of the page currently shown which
+    /// don't change, like the background and the default buttons.
+    fn setup_page(&mut self, commands: &mut Commands, fonts: &Assets<Font>) {
+        let story = match &self.story {
+            Some(story) => story,
+            None => return,
+        };
+        let book = story.book();
+        let page = match story.current_page() {
+            Some(page) => page,
+            None => return,
+        };
+        let page_key = story.page_key();
+        let localization = &self.localization;
+        let line_text = |line_index: usize| {
+            localization.tr(
+                &page.line_id(&page_key, line_index),
+                &page.lines[line_index].text,
+            )
+        };
+
+        let page_style = book.page_style(page);
+        let text_width = page.text_width.or(book.text_width);
+
+        // Split the page into continuation screens if it overflows
+        let screens = match book.overflow {
+            OverflowMode::Scroll => std::iter::once(0..page.lines.len()).collect(),
+            OverflowMode::Paginate => {
+                let line_heights: Vec<f32> = page
+                    .lines
+                    .iter()
+                    .enumerate()
+                    .map(|(line_index, line)| {
+                        let style = book.line_style(page, line);
+                        let font_size = style.size * self.font_scale;
+                        let font_data = layout::font_data(fonts, &self.book_fonts.primary(&style));
+                        let text_height = layout::text_height(
+                            font_data,
+                            font_size,
+                            &line_text(line_index),
+                            text_width,
+                        );
+                        text_height + book.line_spacing * 2.
+                    })
+                    .collect();
+                let button_count = page
+                    .buttons
+                    .as_ref()
+                    .unwrap_or(&book.default_buttons)
+                    .len()
+                    .max(1);
+                let buttons_height = button_count as f32 * (BUTTON_HEIGHT + book.line_spacing * 2.);
+                layout::paginate(&line_heights, self.content_height() - buttons_height)
+            }
+        };
+        let screen_index = self.screen_index.min(screens.len() - 1);
+        let lines = screens[screen_index].clone();
+        let is_last_screen = screen_index + 1 == screens.len();
+
+        // Describe the items of the screen, to compare them with the ones shown
+        let mut items = vec![];
+        for line_index in lines {
+            let line = &page.lines[line_index];
+            items.push(PageItem::Line {
+                index: line_index,
+                text: line_text(line_index).into_owned(),
+                style: book.line_style(page, line),
+                text_width,
+            });
+        }
+        if is_last_screen {
+            let buttons = page.buttons.as_ref().unwrap_or(&book.default_buttons);
+            let mut buttons: Vec<_> = buttons.iter().collect();
+            buttons.sort_by(|a, b| a.0.cmp(b.0));
+            for (color, button) in buttons {
+                let id = if page.buttons.is_some() {
+                    page.button_id(&page_key, color)
+                } else {
+                    book.button_id(None, color)
+                };
+                items.push(PageItem::Button {
+                    text: localization.tr(&id, &button.text).into_owned(),
+                    style: book.button_style(page, button),
+                    image: self.buttons.get(color).cloned().unwrap_or_default(),
+                });
+            }
+
+            // Language picker
+            if story.is_first_page() && localization.has_translations() {
+                items.push(PageItem::LanguagePicker {
+                    text: format!("◀ {} ▶", localization.name()),
+                    style: page_style.clone(),
+                });
+            }
+        } else {
+            items.push(PageItem::Button {
+                text: localization.tr_ui("ui.continue").into_owned(),
+                style: page_style.clone(),
+                image: self.buttons.get("space").cloned().unwrap_or_default(),
+            });
+        }
+
+        let frame = PageFrame {
+            background_color: page_style.background_color,
+            align: page.align,
+        };
+        let overflow = book.overflow;
+        let line_spacing = book.line_spacing;
+
+        let mut page_nodes = match self.page_nodes.take() {
+            Some(page_nodes) if page_nodes.frame == frame => {
+                if overflow == OverflowMode::Scroll {
+                    // Scroll back to the top
+                    commands
+                        .entity(page_nodes.container)
+                        .insert(self.scroll_style(&frame))
+                        .insert(ScrollContent::default());
+                }
+                page_nodes
+            }
+            _ => {
+                self.clear(commands);
+                self.spawn_frame(commands, frame, overflow, &page_style, fonts)
+            }
+        };
+
+        // Reuse the entities of the items shown in both screens, and spawn the others
+        let mut previous_items = std::mem::take(&mut page_nodes.items);
+        commands
+            .entity(page_nodes.container)
+            .with_children(|parent| {
+                for item in items {
+                    let previous = previous_items
+                        .iter()
+                        .position(|(previous, _)| *previous == item);
+                    let entity = match previous {
+                        Some(index) => previous_items.remove(index).1,
+                        None => self.spawn_item(parent, &item, line_spacing, fonts),
+                    };
+                    page_nodes.items.push((item, entity));
+                }
+            });
+        for (_, entity) in previous_items {
+            commands.entity(entity).despawn_recursive();
+        }
+        // Put the items back in display order
+        let entities: Vec<Entity> = page_nodes.items.iter().map(|(_, entity)| *entity).collect();
+        commands
+            .entity(page_nodes.container)
+            .remove_children(&entities)
+            .push_children(&entities);
+
+        self.screens = screens;
+        self.screen_index = screen_index;
+        self.page_nodes = Some(page_nodes);
+    }
+
+    /// Spawn the nodes framing the items of a page: the background, the content node, and
+    /// with [`OverflowMode::Scroll`] the scroll node and its indicators.
+    fn spawn_frame(
+        &mut self,
+        commands: &mut Commands,
+        frame: PageFrame,
+        overflow: OverflowMode,
+        page_style: &ResolvedStyle,
+        fonts: &Assets<Font>,
+    ) -> PageNodes {
+        let justify_content = match overflow {
+            OverflowMode::Scroll => Some(JustifyContent::FlexStart),
+            OverflowMode::Paginate => frame.align,
+        };
+        let (root_node, mut content) =
+            self.spawn_background(commands, frame.background_color, justify_content);
+
+        let container = match overflow {
+            OverflowMode::Scroll => {
+                let mut container = None;
+                content.with_children(|parent| {
+                    let scroll_node = parent
+                        .spawn_bundle(NodeBundle {
+                            style: self.scroll_style(&frame),
+                            color: UiColor(Color::NONE),
+                            ..Default::default()
+                        })
+                        .insert(Name::new("Scroll"))
+                        .insert(ScrollContent::default())
+                        .id();
+                    container = Some(scroll_node);
+                    self.spawn_scroll_indicator(parent, ScrollIndicator::Up, page_style, fonts);
+                    self.spawn_scroll_indicator(parent, ScrollIndicator::Down, page_style, fonts);
+                });
+                container.unwrap()
+            }
+            OverflowMode::Paginate => content.id(),
+        };
+
+        self.root_node = Some(root_node);
+        PageNodes {
+            frame,
+            container,
+            items: vec![],
+        }
+    }
+
+    /// Style of the scroll node of a page, scrolled to the top.
+    fn scroll_style(&self, frame: &PageFrame) -> Style {
+        Style {
+            // Fill at least the entire viewport, so that the page align applies if the
+            // content doesn't overflow
+            min_size: Size {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+            },
+            flex_shrink: 0.,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: frame.align.unwrap_or(JustifyContent::FlexStart),
+            align_items: AlignItems::Center,
+            ..Default::default()
+        }
+    }
+
+    /// Spawn an item of a page, and return its entity.
+    fn spawn_item(
+        &self,
+        parent: &mut ChildBuilder,
+        item: &PageItem,
+        line_spacing: f32,
+        fonts: &Assets<Font>,
+    ) -> Entity {
+        let margin = self.px(line_spacing);
+        let margin = Rect {
+            top: margin,
+            bottom: margin,
+            ..Default::default()
+        };
+        match item {
+            PageItem::Line {
+                index,
+                text,
+                style,
+                text_width,
+            } => self
+                .spawn_line(parent, text, style, *text_width, margin, fonts)
+                .insert(Name::new(format!("Line{}", index)))
+                .id(),
+            PageItem::Button { text, style, image } => {
+                self.spawn_button(parent, line_spacing, text, style, image.clone(), fonts)
+            }
+            PageItem::LanguagePicker { text, style } => parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin,
+                        ..Default::default()
+                    },
+                    text: self.text(
+                        text,
+                        style,
+                        self.font_size(style.size),
+                        TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            vertical: VerticalAlign::Center,
+                        },
+                        fonts,
+                    ),
+                    ..Default::default()
+                })
+                .insert(Name::new("LanguagePicker"))
+                .id(),
+        }
+    }
+
+    /// Spawn an indicator at the top or bottom edge of the viewport, shown when more
+    /// content is available by scrolling in that direction.
+    fn spawn_scroll_indicator(
+        &self,
+        parent: &mut ChildBuilder,
+        indicator: ScrollIndicator,
+        style: &ResolvedStyle,
+        fonts: &Assets<Font>,
+    ) {
+        let (text, position) = match indicator {
+            ScrollIndicator::Up => (
+                "▲",
+                Rect {
+                    // Flex bottom is the screen top (upside down)
+                    bottom: self.px(10.),
+                    ..Default::default()
+                },
+            ),
+            ScrollIndicator::Down => (
+                "▼",
+                Rect {
+                    top: self.px(10.),
+                    ..Default::default()
+                },
+            ),
+        };
+        parent
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    ..Default::default()
+                },
+                text: self.text(
+                    text,
+                    style,
+                    self.font_size(style.size),
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Center,
+                    },
+                    fonts,
+                ),
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(Name::new("ScrollIndicator"))
+            .insert(indicator);
+    }
+
+    /// Spawn a single line of text.
+    ///
+    /// With a text width, the line node is a column of that width centered on the page and
+    /// the text wraps inside it; otherwise the line node itself is placed according to
+    /// the style alignment. Justified text needs the font data to break rows, and falls back
+    /// to [`TextAlign::Start`] if not available.
+    ///
+    /// Alignment is mirrored for right-to-left languages. Text needing bidirectional
+    /// reordering is broken into rows before reordering, so that rows read in order from top
+    /// to bottom.
+    fn spawn_line<'w, 's, 'a>(
+        &self,
+        parent: &'a mut ChildBuilder<'w, 's, '_>,
+        text: &str,
+        style: &ResolvedStyle,
+        text_width: Option<f32>,
+        margin: Rect<Val>,
+        fonts: &Assets<Font>,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let direction = self.localization.direction();
+        let align = style.align.for_direction(direction);
+        let font_size = self.font_size(style.size);
+        let font_data = layout::font_data(fonts, &self.book_fonts.primary(style));
+        let text_width = text_width.map(|text_width| text_width * self.ui_scale);
+
+        let justified = match (align, text_width, font_data) {
+            (TextAlign::Justify, Some(text_width), Some(font_data)) => {
+                Some((text_width, font_data))
+            }
+            _ => None,
+        };
+        let align = if align == TextAlign::Justify && justified.is_none() {
+            TextAlign::Start.for_direction(direction)
+        } else {
+            align
+        };
+        let text_align = TextAlignment {
+            horizontal: align.horizontal_align(),
+            vertical: VerticalAlign::Center,
+        };
+
+        let mut entity_commands = parent.spawn_bundle(NodeBundle {
+            style: Style {
+                margin,
+                // Justified rows are laid out from top to bottom (reverse because Bevy)
+                flex_direction: if justified.is_some() {
+                    FlexDirection::ColumnReverse
+                } else {
+                    FlexDirection::Row
+                },
+                align_self: if text_width.is_some() {
+                    AlignSelf::Center
+                } else {
+                    align.align_self()
+                },
+                justify_content: align.justify_content(),
+                size: Size {
+                    width: text_width.map_or(Val::Auto, Val::Px),
+                    height: Val::Auto,
+                },
+                ..Default::default()
+            },
+            color: UiColor(Color::NONE),
+            ..Default::default()
+        });
+
+        match justified {
+            Some((text_width, font_data)) => {
+                entity_commands.with_children(|parent| {
+                    for paragraph in text.split('\n') {
+                        let rows = layout::wrap_words(font_data, font_size, paragraph, text_width);
+                        let row_count = rows.len();
+                        for (row_index, row) in rows.into_iter().enumerate() {
+                            // The last row of a paragraph, and rows with a single word, are
+                            // not stretched.
+                            let is_stretched = row_index + 1 < row_count && row.len() > 1;
+                            let row_start = match direction {
+                                TextDirection::LeftToRight => JustifyContent::FlexStart,
+                                TextDirection::RightToLeft => JustifyContent::FlexEnd,
+                            };
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        flex_direction: FlexDirection::Row,
+                                        justify_content: if is_stretched {
+                                            JustifyContent::SpaceBetween
+                                        } else {
+                                            row_start
+                                        },
+                                        size: Size {
+                                            width: Val::Px(text_width),
+                                            height: Val::Auto,
+                                        },
+                                        min_size: Size {
+                                            width: Val::Auto,
+                                            height: Val::Px(font_size),
+                                        },
+                                        ..Default::default()
+                                    },
+                                    color: UiColor(Color::NONE),
+                                    ..Default::default()
+                                })
+                                .with_children(|parent| {
+                                    // Reorder the entire row, then split it into words
+                                    // already in visual order
+                                    let row = row.join(" ");
+                                    let row = bidi::reorder(&row, direction);
+                                    let words = if is_stretched {
+                                        row.split(' ').collect()
+                                    } else {
+                                        vec![row.as_ref()]
+                                    };
+                                    for word in words {
+                                        parent.spawn_bundle(TextBundle {
+                                            text: self.visual_text(
+                                                word, style, font_size, text_align, fonts,
+                                            ),
+                                            ..Default::default()
+                                        });
+                                    }
+                                });
+                        }
+                    }
+                });
+            }
+            None => {
+                // Break rows before reordering, as the text pipeline would wrap the
+                // reordered text from its visual start
+                let text = match (text_width, font_data) {
+                    (Some(text_width), Some(font_data)) if bidi::needs_reorder(text, direction) => {
+                        Cow::Owned(
+                            text.split('\n')
+                                .flat_map(|paragraph| {
+                                    layout::wrap_words(font_data, font_size, paragraph, text_width)
+                                })
+                                .map(|row| row.join(" "))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        )
+                    }
+                    _ => Cow::Borrowed(text),
+                };
+                entity_commands.with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            // Wrap text inside the text column, if any
+                            max_size: Size {
+                                width: text_width.map_or(Val::Auto, Val::Px),
+                                height: Val::Auto,
+                            },
+                            ..Default::default()
+                        },
+                        text: self.text(&text, style, font_size, text_align, fonts),
+                        ..Default::default()
+                    });
+                });
+            }
+        }
+
+        entity_commands
+    }
+
+    fn spawn_button(
+        &self,
+        parent: &mut ChildBuilder,
+        line_spacing: f32,
+        text: &str,
+        style: &ResolvedStyle,
+        image: Handle<Image>,
+        fonts: &Assets<Font>,
+    ) -> Entity {
+        let margin = self.px(line_spacing);
+        let margin = Rect {
+            top: margin,
+            bottom: margin,
+            ..Default::default()
+        };
+        // Mirror the button image and text for right-to-left languages
+        let direction = self.localization.direction();
+        let flex_row = direction.flex_row();
+        let text_margin = match direction {
+            TextDirection::LeftToRight => Rect {
+                left: self.px(20.),
+                ..Default::default()
+            },
+            TextDirection::RightToLeft => Rect {
+                right: self.px(20.),
+                ..Default::default()
+            },
+        };
+
+        parent
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: flex_row,
+                    align_items: AlignItems::Center,
+                    margin,
+                    size: Size {
+                        width: Val::Auto,
+                        height: self.px(BUTTON_HEIGHT),
+                    },
+                    ..Default::default()
+                },
+                color: UiColor(Color::NONE),
+                ..Default::default()
+            })
+            .insert(Name::new(format!("button:{}", text)))
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            // Align button image (child) toward the text
+                            justify_content: JustifyContent::FlexEnd,
+                            flex_direction: flex_row,
+                            align_items: AlignItems::Center,
+                            size: Size {
+                                width: self.px(350.),
+                                height: self.px(BUTTON_HEIGHT),
+                            },
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .insert(Name::new("image"))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(ImageBundle {
+                            image: UiImage(image),
+                            image_mode: ImageMode::KeepAspect,
+                            style: Style {
+                                size: Size {
+                                    width: Val::Auto,
+                                    height: Val::Auto,
+                                },
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        });
+                    });
+
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            flex_direction: flex_row,
+                            align_items: AlignItems::Center,
+                            margin: text_margin,
+                            size: Size {
+                                width: self.px(300.),
+                                height: self.px(BUTTON_HEIGHT),
+                            },
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .insert(Name::new("text"))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: self.text(
+                                text,
+                                style,
+                                self.font_size(style.size),
+                                TextAlignment {
+                                    horizontal: HorizontalAlign::Center,
+                                    vertical: VerticalAlign::Center,
+                                },
+                                fonts,
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            })
+            .id()
+    }
+
+    /// End the current run, record its score, and show the leaderboard.
+    pub(crate) fn end_run(&mut self, commands: &mut Commands, fonts: &Assets<Font>) {
+        // Insert new score, retaining only the 10 last ones.
+        while self.scores.len() >= 10 {
+            self.scores.remove(0);
+        }
+        let score = Score {
+            page_read: self.page_read,
+            date: (self.clock)(),
+        };
+        self.scores.push(score);
+        self.events.push(StoryEvent::RunFinished(score));
+
+        self.spawn_leaderboard(commands, fonts);
+    }
+
+    /// Spawn the leaderboard at the end of the game.
+    fn spawn_leaderboard(&mut self, commands: &mut Commands, fonts: &Assets<Font>) {
+        self.clear(commands);
+
+        // Sort score records by actual score value (pages read)
+        let mut sorted_scores = self.scores.clone();
+        sorted_scores.sort_by(|a, b| b.page_read.partial_cmp(&a.page_read).unwrap());
+
+        let style = &self.book().unwrap().scoreboard_style();
+
+        let (root_node, mut content) = self.spawn_background(
+            commands,
+            style.background_color,
+            Some(JustifyContent::FlexStart),
+        );
+
+        // Mirror the score columns for right-to-left languages
+        let flex_row = self.localization.direction().flex_row();
+
+        let text_align = TextAlignment {
+            horizontal: HorizontalAlign::Center,
+            vertical: VerticalAlign::Center,
+        };
+
+        content.with_children(|parent| {
+            // Title
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect {
+                            top: self.px(30.),
+                            bottom: self.px(30.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: self.text(
+                        &self.localization.tr_ui("ui.score"),
+                        style,
+                        self.font_size(60.),
+                        text_align,
+                        fonts,
+                    ),
+                    ..Default::default()
+                })
+                .insert(Name::new("Score"));
+
+            // Score lines
+            let margin = self.px(10.);
+            let margin = Rect {
+                top: margin,
+                bottom: margin,
+                ..Default::default()
+            };
+            for score in &sorted_scores {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            margin,
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(format!("{:?}", score.date)))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    flex_direction: flex_row,
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                color: UiColor(Color::NONE),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn_bundle(NodeBundle {
+                                        style: Style {
+                                            flex_direction: flex_row,
+                                            justify_content: JustifyContent::FlexStart,
+                                            size: Size {
+                                                width: self.px(400.),
+                                                height: self.px(30.),
+                                            },
+                                            ..Default::default()
+                                        },
+                                        color: UiColor(Color::NONE),
+                                        ..Default::default()
+                                    })
+                                    .with_children(|parent| {
+                                        parent.spawn_bundle(TextBundle {
+                                            text: self.text(
+                                                &score.date.format("%Y-%m-%d %H:%M:%S").to_string(),
+                                                style,
+                                                self.font_size(style.size),
+                                                text_align,
+                                                fonts,
+                                            ),
+                                            ..Default::default()
+                                        });
+                                    });
+
+                                parent
+                                    .spawn_bundle(NodeBundle {
+                                        style: Style {
+                                            flex_direction: flex_row,
+                                            justify_content: JustifyContent::FlexEnd,
+                                            size: Size {
+                                                width: self.px(200.),
+                                                height: self.px(30.),
+                                            },
+                                            ..Default::default()
+                                        },
+                                        color: UiColor(Color::NONE),
+                                        ..Default::default()
+                                    })
+                                    .with_children(|parent| {
+                                        parent.spawn_bundle(TextBundle {
+                                            text: self.text(
+                                                &self.localization.tr_ui_args(
+                                                    "ui.pages_read",
+                                                    &[(
+                                                        "pages_read",
+                                                        FluentValue::from(score.page_read),
+                                                    )],
+                                                ),
+                                                style,
+                                                self.font_size(style.size),
+                                                text_align,
+                                                fonts,
+                                            ),
+                                            ..Default::default()
+                                        });
+                                    });
+                            });
+                    });
+            }
+
+            // Restart button
+            self.spawn_button(
+                parent,
+                30.,
+                &self.localization.tr_ui("ui.restart"),
+                style,
+                self.buttons.get("space").cloned().unwrap_or_default(),
+                fonts,
+            );
+        });
+
+        self.root_node = Some(root_node);
+    }
+
+    /// Spawn the pause menu over the current content.
+    pub(crate) fn spawn_pause_menu(&mut self, commands: &mut Commands, fonts: &Assets<Font>) {
+        let title = self.localization.tr_ui("ui.paused").into_owned();
+        let lines = [
+            self.localization.tr_ui("ui.resume").into_owned(),
+            self.localization.tr_ui("ui.open_settings").into_owned(),
+        ];
+        self.spawn_menu(commands, fonts, &title, &lines);
+    }
+
+    /// Spawn the settings menu over the current content, with the current font size and
+    /// language.
+    pub(crate) fn spawn_settings_menu(&mut self, commands: &mut Commands, fonts: &Assets<Font>) {
+        let title = self.localization.tr_ui("ui.settings").into_owned();
+        let font_size = (self.font_scale * 100.).round() as i64;
+        let mut lines = vec![self
+            .localization
+            .tr_ui_args(
+                "ui.font_size",
+                &[("font_size", FluentValue::from(font_size))],
+            )
+            .into_owned()];
+        if self.localization.has_translations() {
+            let language = self.localization.name().to_string();
+            lines.push(
+                self.localization
+                    .tr_ui_args("ui.language", &[("language", FluentValue::from(language))])
+                    .into_owned(),
+            );
+        }
+        lines.push(self.localization.tr_ui("ui.close_settings").into_owned());
+        self.spawn_menu(commands, fonts, &title, &lines);
+    }
+
+    /// Spawn a menu with a title and lines of text over the current content, replacing
+    /// any other menu.
+    fn spawn_menu(
+        &mut self,
+        commands: &mut Commands,
+        fonts: &Assets<Font>,
+        title: &str,
+        lines: &[String],
+    ) {
+        self.clear_overlay(commands);
+        let root_node = match self.root_node {
+            Some(root_node) => root_node,
+            None => return,
+        };
+        let style = &self.book().unwrap().base_style();
+        let text_align = TextAlignment {
+            horizontal: HorizontalAlign::Center,
+            vertical: VerticalAlign::Center,
+        };
+
+        let overlay = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    // Cover the entire content, drawn over it as the last child
+                    position: Rect::all(Val::Px(0.0)),
+                    size: Size {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                    },
+                    flex_direction: FlexDirection::ColumnReverse,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: UiColor(MENU_COLOR),
+                ..Default::default()
+            })
+            .insert(Name::new("Menu"))
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect {
+                            top: self.px(30.),
+                            bottom: self.px(30.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: self.text(title, style, self.font_size(60.), text_align, fonts),
+                    ..Default::default()
+                });
+                for line in lines {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect {
+                                top: self.px(10.),
+                                bottom: self.px(10.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        text: self.text(line, style, self.font_size(style.size), text_align, fonts),
+                        ..Default::default()
+                    });
+                }
+            })
+            .id();
+        commands.entity(root_node).push_children(&[overlay]);
+        self.overlay_node = Some(overlay);
+    }
+
+    /// Clear the menu shown over the current content, if any.
+    pub(crate) fn clear_overlay(&mut self, commands: &mut Commands) {
+        if let Some(entity) = self.overlay_node.take() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    /// Spawn a background node of the given color covering the entire screen, with a
+    /// content node of the size of the scaled design resolution, and set up to have
+    /// children laid out in column from top to bottom, horizontally stretching the entire
+    /// content node.
+    ///
+    /// Returns the background entity, to be despawned with all its content, and the
+    /// commands of the content node to spawn children into.
+    fn spawn_background<'w, 's, 'a>(
+        &'a self,
+        commands: &'a mut Commands<'w, 's>,
+        color: Color,
+        justify_content: Option<JustifyContent>,
+    ) -> (Entity, EntityCommands<'w, 's, 'a>) {
+        let (content_size, letterbox_color) = match self.book() {
+            Some(book) if book.scale_mode == ScaleMode::Fit => (
+                Size {
+                    width: self.px(book.design_resolution.width),
+                    height: self.px(book.design_resolution.height),
+                },
+                book.resolve_color(book.letterbox_color.as_ref(), color),
+            ),
+            _ => (
+                Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                color,
+            ),
+        };
+
+        let content = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    size: content_size,
+                    // Lay out content items from top to bottom (reverse because Bevy)
+                    flex_direction: FlexDirection::ColumnReverse,
+                    // Align the entire content group vertically to the top
+                    justify_content: justify_content.unwrap_or(JustifyContent::FlexStart),
+                    // Center child items horizontally
+                    align_items: AlignItems::Center,
+                    // Clip content to the design area
+                    overflow: Overflow::Hidden,
+                    ..Default::default()
+                },
+                color: UiColor(color),
+                ..Default::default()
+            })
+            .insert(Name::new("Content"))
+            .id();
+
+        let background = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    // Cover entire screen
+                    position: Rect::all(Val::Px(0.0)),
+                    size: Size {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                    },
+                    // Center the content node, letterboxing the remaining space
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: UiColor(letterbox_color),
+                ..Default::default()
+            })
+            .insert(Name::new("Background"))
+            .insert(Background)
+            .push_children(&[content])
+            .id();
+
+        (background, commands.entity(content))
+    }
+}
+
+/// Start loading the assets of the story, and create the [`TextSystem`] resource.
+pub(crate) fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    story_assets: Res<StoryAssets>,
+    windows: Res<Windows>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    let content = asset_server.load(story_assets.book.as_str());
+    let font = asset_server.load(story_assets.font.as_str());
+    let buttons = story_assets
+        .button_images
+        .iter()
+        .map(|(key, path)| (key.clone(), asset_server.load(path.as_str())))
+        .collect();
+    let window_size = windows
+        .get_primary()
+        .map_or(Vec2::new(1280., 720.), |window| {
+            Vec2::new(window.width(), window.height())
+        });
+    commands.insert_resource(TextSystem::new(content, font, buttons, window_size));
+}
+
+/// Handle the window size, fonts, strings and font scale, whatever the game state.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update(
+    mut commands: Commands,
+    text_assets: Res<Assets<TextAsset>>,
+    fluent_assets: Res<Assets<FluentAsset>>,
+    fonts: Res<Assets<Font>>,
+    asset_server: Res<AssetServer>,
+    mut text_system: ResMut<TextSystem>,
+    keyboard_input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut window_resized: EventReader<WindowResized>,
+    mut font_events: EventReader<AssetEvent<Font>>,
+) {
+    if let Some(event) = window_resized
+        .iter()
+        .rev()
+        .find(|event| event.id == WindowId::primary())
+    {
+        let window_size = Vec2::new(event.width, event.height);
+        text_system.resize(&mut commands, &fonts, window_size, &state);
+    }
+
+    // Respawn the current content once its fonts are loaded or failed to load, to apply
+    // font fallbacks and text layout
+    let is_font_loaded = font_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } => text_system.book_fonts.contains(handle),
+        _ => false,
+    });
+    let has_font_failed = text_system.book_fonts.update_failed(&asset_server);
+    if is_font_loaded || has_font_failed {
+        text_system.refresh(&mut commands, &fonts, &state);
+    }
+
+    // Respawn the current content once the strings of the current language loaded
+    if text_system.localization.poll(&text_assets, &fluent_assets) {
+        text_system.refresh(&mut commands, &fonts, &state);
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]) {
+        let font_scale = text_system.font_scale + 0.1;
+        text_system.set_font_scale(&mut commands, &fonts, font_scale, &state);
+    } else if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        let font_scale = text_system.font_scale - 0.1;
+        text_system.set_font_scale(&mut commands, &fonts, font_scale, &state);
+    }
+}
@@ -0,0 +1,466 @@
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::book::*;
+
+/// Result of importing a Twee source into a book.
+pub struct TweeImport {
+    pub book: Book,
+    /// Features of the source which were not converted, like story format macros.
+    pub warnings: Vec<String>,
+}
+
+/// Single passage of a Twee source.
+struct Passage {
+    name: String,
+    tags: Vec<String>,
+    text: String,
+}
+
+/// Content of the `StoryData` special passage.
+#[derive(Deserialize)]
+struct StoryData {
+    start: Option<String>,
+}
+
+/// Remove the backslash escapes of a passage header.
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(c) = chars.next() {
+                result.push(c);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Parse a passage header, without the leading `::`, into its name and tags.
+///
+/// The optional metadata block is ignored.
+fn parse_header(header: &str) -> (String, Vec<String>) {
+    let mut name_end = header.len();
+    let mut tags = vec![];
+    let mut escaped = false;
+    for (index, c) in header.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' | '{' => {
+                name_end = index;
+                if c == '[' {
+                    if let Some(end) = header[index..].find(']') {
+                        tags = header[index + 1..index + end]
+                            .split_whitespace()
+                            .map(unescape)
+                            .collect();
+                    }
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    (unescape(header[..name_end].trim()), tags)
+}
+
+/// Split a Twee source into its passages.
+fn parse_passages(source: &str) -> Vec<Passage> {
+    let mut passages: Vec<Passage> = vec![];
+    for line in source.lines() {
+        if let Some(header) = line.strip_prefix("::") {
+            let (name, tags) = parse_header(header);
+            passages.push(Passage {
+                name,
+                tags,
+                text: String::new(),
+            });
+        } else if let Some(passage) = passages.last_mut() {
+            passage.text.push_str(line);
+            passage.text.push('\n');
+        }
+    }
+    passages
+}
+
+/// Parse the content of a link, between `[[` and `]]`, into its text and target passage.
+fn parse_link(link: &str) -> (String, String) {
+    let (text, target) = if let Some((text, target)) = link.split_once('|') {
+        (text, target)
+    } else if let Some((text, target)) = link.rsplit_once("->") {
+        (text, target)
+    } else if let Some((target, text)) = link.split_once("<-") {
+        (text, target)
+    } else {
+        (link, link)
+    };
+    (text.trim().to_string(), target.trim().to_string())
+}
+
+/// Find the end of a Harlowe macro starting at `start`, just after its opening parenthesis,
+/// accounting for nested parentheses.
+fn find_closing_paren(s: &str, start: usize) -> Option<usize> {
+    let mut depth = 1;
+    for (index, c) in s[start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Convert a single row of passage text, collecting its links and the unsupported
+/// features it contains.
+///
+/// Link markup is replaced by the link text, and macros are removed. Rows made only of
+/// links are dropped entirely, as the links already become buttons.
+fn convert_row(
+    row: &str,
+    links: &mut Vec<(String, String)>,
+    unsupported: &mut Vec<String>,
+) -> String {
+    let mut text = String::new();
+    let mut has_prose = false;
+    let mut rest = row;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("[[") {
+            if let Some(end) = after.find("]]") {
+                let (link_text, target) = parse_link(&after[..end]);
+                text.push_str(&link_text);
+                links.push((link_text, target));
+                rest = &after[end + 2..];
+                continue;
+            }
+        } else if let Some(after) = rest.strip_prefix("<<") {
+            if let Some(end) = after.find(">>") {
+                let name = after[..end].split_whitespace().next().unwrap_or_default();
+                unsupported.push(format!("macro <<{}>>", name));
+                rest = &after[end + 2..];
+                continue;
+            }
+        } else if let Some(after) = rest.strip_prefix('(') {
+            // Harlowe macro, like `(set: $x to 1)`
+            let name_len = after
+                .find(|c: char| !(c.is_alphanumeric() || c == '-'))
+                .unwrap_or(after.len());
+            if name_len > 0 && after[name_len..].starts_with(':') {
+                if let Some(end) = find_closing_paren(after, 0) {
+                    unsupported.push(format!("macro ({}:)", &after[..name_len]));
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        } else if let Some(after) = rest.strip_prefix('$') {
+            let name_len = after
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            if name_len > 0 {
+                unsupported.push(format!("variable ${}", &after[..name_len]));
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        has_prose |= !c.is_whitespace();
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if has_prose {
+        // Collapse the whitespace left by removed macros
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        String::new()
+    }
+}
+
+/// Import a Twee 3 source into a book.
+///
+/// Each passage becomes a page of the same name, and each non-empty row of its text a
+/// line. Links become buttons jumping to the target page: a single link uses the space
+/// key, and multiple links the number keys. Passages without links are final pages. The
+/// `end` tag also marks a final page, and the first other tag becomes the page style.
+///
+/// Story format macros are removed, and variables kept as is; both are reported as
+/// warnings.
+pub fn import(source: &str) -> Result<TweeImport, String> {
+    let mut warnings = vec![];
+    let mut book = Book::default();
+    book.default_buttons.insert(
+        "space".to_string(),
+        Button {
+            id: None,
+            text: "Continue".to_string(),
+            action: ButtonAction::NextPage,
+            style: None,
+        },
+    );
+
+    let mut start = None;
+    let mut passages = vec![];
+    for passage in parse_passages(source) {
+        match passage.name.as_str() {
+            "StoryTitle" => {}
+            "StoryData" => {
+                let data: StoryData = serde_json::from_str(&passage.text)
+                    .map_err(|err| format!("Invalid StoryData: {}", err))?;
+                start = data.start;
+            }
+            _ if passage.tags.iter().any(|tag| tag == "Twine.private") => {}
+            _ if passage
+                .tags
+                .iter()
+                .any(|tag| tag == "script" || tag == "stylesheet") =>
+            {
+                warnings.push(format!(
+                    "Passage '{}': unsupported script or stylesheet, ignored",
+                    passage.name
+                ));
+            }
+            _ => passages.push(passage),
+        }
+    }
+    if passages.is_empty() {
+        return Err("No story passage".to_string());
+    }
+
+    // The story starts on the first page
+    let start = start.unwrap_or_else(|| "Start".to_string());
+    if let Some(index) = passages.iter().position(|passage| passage.name == start) {
+        let passage = passages.remove(index);
+        passages.insert(0, passage);
+    }
+
+    let mut targets = vec![];
+    for passage in &passages {
+        let mut links = vec![];
+        let mut unsupported = vec![];
+        let lines = passage
+            .text
+            .lines()
+            .map(|row| convert_row(row, &mut links, &mut unsupported))
+            .filter(|row| !row.is_empty())
+            .map(|text| Line {
+                text,
+                ..Default::default()
+            })
+            .collect();
+        // Report each feature once per passage
+        let mut reported = HashSet::new();
+        unsupported.retain(|feature| reported.insert(feature.clone()));
+        for feature in unsupported {
+            warnings.push(format!(
+                "Passage '{}': unsupported {}",
+                passage.name, feature
+            ));
+        }
+
+        if links.len() > CHOICE_KEYS.len() {
+            warnings.push(format!(
+                "Passage '{}': only the first {} links are converted, out of {}",
+                passage.name,
+                CHOICE_KEYS.len(),
+                links.len()
+            ));
+            links.truncate(CHOICE_KEYS.len());
+        }
+        let is_single_link = links.len() == 1;
+        let buttons: HashMap<String, Button> = links
+            .into_iter()
+            .enumerate()
+            .map(|(index, (text, target))| {
+                let key = if is_single_link {
+                    "space"
+                } else {
+                    CHOICE_KEYS[index]
+                };
+                targets.push((passage.name.clone(), target.clone()));
+                let button = Button {
+                    id: None,
+                    text,
+                    action: ButtonAction::JumpToPage(target),
+                    style: None,
+                };
+                (key.to_string(), button)
+            })
+            .collect();
+
+        let mut tags = passage.tags.iter().filter(|tag| *tag != "end");
+        let style = tags.next().cloned();
+        if let Some(style) = &style {
+            book.styles.entry(style.clone()).or_default();
+        }
+        for tag in tags {
+            warnings.push(format!(
+                "Passage '{}': tag '{}' ignored, only the first tag becomes the page style",
+                passage.name, tag
+            ));
+        }
+
+        book.pages.push(Page {
+            name: Some(passage.name.clone()),
+            is_final: buttons.is_empty() || passage.tags.iter().any(|tag| tag == "end"),
+            lines,
+            buttons: if buttons.is_empty() {
+                None
+            } else {
+                Some(buttons)
+            },
+            style,
+            ..Default::default()
+        });
+    }
+
+    for (name, target) in targets {
+        if !passages.iter().any(|passage| passage.name == target) {
+            warnings.push(format!(
+                "Passage '{}': link to unknown passage '{}'",
+                name, target
+            ));
+        }
+    }
+
+    Ok(TweeImport { book, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the buttons of a page, as sorted key, text and target page triples.
+    fn buttons(page: &Page) -> Vec<(&str, &str, &str)> {
+        let mut buttons: Vec<_> = page
+            .buttons
+            .iter()
+            .flatten()
+            .map(|(key, button)| match &button.action {
+                ButtonAction::JumpToPage(target) => {
+                    (key.as_str(), button.text.as_str(), target.as_str())
+                }
+                _ => panic!("Unexpected action of button {}", key),
+            })
+            .collect();
+        buttons.sort();
+        buttons
+    }
+
+    fn lines(page: &Page) -> Vec<&str> {
+        page.lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn links_become_buttons() {
+        let source = "\
+:: Start
+You wake up.
+[[Get up->Hall]] or [[Bed<-Stay in bed]]
+
+:: Hall
+[[Bed]]
+
+:: Bed
+Good night.
+";
+        let import = import(source).unwrap();
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+        let pages = &import.book.pages;
+        assert_eq!(
+            lines(&pages[0]),
+            vec!["You wake up.", "Get up or Stay in bed"]
+        );
+        assert_eq!(
+            buttons(&pages[0]),
+            vec![("1", "Get up", "Hall"), ("2", "Stay in bed", "Bed")]
+        );
+        assert!(!pages[0].is_final);
+
+        // Rows made only of links are dropped
+        assert!(lines(&pages[1]).is_empty());
+        assert_eq!(buttons(&pages[1]), vec![("space", "Bed", "Bed")]);
+
+        assert!(pages[2].is_final);
+        assert!(pages[2].buttons.is_none());
+
+        // Up to 9 links become buttons
+        let links: String = (1..=10).map(|i| format!("[[{}->Start]]", i)).collect();
+        let import = super::import(&format!(":: Start\n{}\n", links)).unwrap();
+        let keys: Vec<_> = buttons(&import.book.pages[0])
+            .into_iter()
+            .map(|(key, text, _)| (key, text))
+            .collect();
+        let expected: Vec<_> = CHOICE_KEYS.iter().map(|key| (*key, *key)).collect();
+        assert_eq!(keys, expected);
+        assert_eq!(
+            import.warnings,
+            vec!["Passage 'Start': only the first 9 links are converted, out of 10"]
+        );
+    }
+
+    #[test]
+    fn headers_with_tags_and_metadata() {
+        let source = "\
+:: StoryData
+{ \"start\": \"Room [1]\" }
+
+:: Hall {\"position\":\"100,100\"}
+Nothing here.
+
+:: Room \\[1\\] [dark end] {\"position\":\"200,100\",\"size\":\"100,200\"}
+A dark room.
+[[Hall]]
+";
+        let import = import(source).unwrap();
+        let pages = &import.book.pages;
+        // The start passage comes first
+        assert_eq!(pages[0].name.as_deref(), Some("Room [1]"));
+        assert_eq!(pages[0].style.as_deref(), Some("dark"));
+        assert!(import.book.styles.contains_key("dark"));
+        assert!(pages[0].is_final);
+        assert_eq!(pages[1].name.as_deref(), Some("Hall"));
+        assert_eq!(pages[1].style, None);
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+
+        let import = super::import(":: Hall [a b]\nText\n").unwrap();
+        assert_eq!(import.book.pages[0].style.as_deref(), Some("a"));
+        assert_eq!(
+            import.warnings,
+            vec!["Passage 'Hall': tag 'b' ignored, only the first tag becomes the page style"]
+        );
+    }
+
+    #[test]
+    fn warns_about_unsupported_features() {
+        let source = "\
+:: Start
+You have $gold coins.<<set $gold to 1>>
+(if: $gold > 0)[Rich] <<set $gold to 2>>
+[[Shop]]
+
+:: Shop
+Closed.
+";
+        let import = import(source).unwrap();
+        assert_eq!(
+            lines(&import.book.pages[0]),
+            vec!["You have $gold coins.", "[Rich]"]
+        );
+        assert_eq!(
+            import.warnings,
+            vec![
+                "Passage 'Start': unsupported variable $gold",
+                "Passage 'Start': unsupported macro <<set>>",
+                "Passage 'Start': unsupported macro (if:)",
+            ]
+        );
+
+        assert!(super::import(":: StoryTitle\nNo story\n").is_err());
+    }
+}